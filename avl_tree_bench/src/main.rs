@@ -0,0 +1,161 @@
+//! AVL木のノードをBoxで確保する場合とアリーナで確保する場合の速度比較
+//! `cargo run --release`で実行する
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use avl_tree_set::AvlTreeSetIn;
+use avl_tree_set_i32::AVLTreeSetIn;
+use avl_tree_vec::AvlTreeVecIn;
+use rand::{rng, Rng};
+
+const N: usize = 1_000_000;
+
+/// BoxとアリーナをROUNDS回ずつ交互に実行し，各操作の最小の実行時間を比べる
+const ROUNDS: usize = 3;
+
+/// 各操作の実行時間を順に記録する
+#[derive(Default)]
+struct Timer(Vec<(&'static str, Duration)>);
+
+impl Timer {
+    fn measure(&mut self, name: &'static str, f: impl FnOnce()) {
+        let start = Instant::now();
+        f();
+        self.0.push((name, start.elapsed()));
+    }
+}
+
+/// 2つのTimerのうち各操作の短い方の実行時間を残す
+fn min(a: Timer, b: Timer) -> Timer {
+    Timer(
+        a.0.into_iter()
+            .zip(b.0)
+            .map(|((name, x), (_, y))| (name, x.min(y)))
+            .collect(),
+    )
+}
+
+/// boxedとarenaを交互に実行し，各操作の実行時間を並べて表示する
+fn compare(title: &str, mut boxed: impl FnMut() -> Timer, mut arena: impl FnMut() -> Timer) {
+    let (mut b, mut a) = (boxed(), arena());
+    for _ in 1..ROUNDS {
+        b = min(b, boxed());
+        a = min(a, arena());
+    }
+    println!("{}", title);
+    println!("{:<16}{:>12}{:>12}", "", "Box", "arena");
+    for ((name, b), (_, a)) in b.0.into_iter().zip(a.0) {
+        println!("{:<16}{:>12.2?}{:>12.2?}", name, b, a);
+    }
+    println!();
+}
+
+fn bench_set<S: avl_tree_set::Storage>(values: &[i32]) -> Timer {
+    let mut timer = Timer::default();
+    let mut set = AvlTreeSetIn::<i32, S>::new();
+    timer.measure("insert", || {
+        values.iter().for_each(|&x| {
+            set.insert(x);
+        })
+    });
+    timer.measure("contains", || {
+        values.iter().for_each(|x| {
+            black_box(set.contains(x));
+        })
+    });
+    timer.measure("iter", || {
+        black_box(set.iter().map(|&x| x as i64).sum::<i64>());
+    });
+    timer.measure("remove half", || {
+        values.iter().step_by(2).for_each(|x| {
+            set.remove(x);
+        })
+    });
+    timer.measure("drop", || drop(set));
+    timer
+}
+
+fn bench_set_i32<S: avl_tree_set_i32::Storage>(values: &[i32]) -> Timer {
+    let mut timer = Timer::default();
+    let mut set = AVLTreeSetIn::<S>::new();
+    timer.measure("insert", || {
+        values.iter().for_each(|&x| {
+            set.insert(x);
+        })
+    });
+    timer.measure("contains", || {
+        values.iter().for_each(|x| {
+            black_box(set.contains(x));
+        })
+    });
+    timer.measure("iter", || {
+        black_box(set.iter().map(|&x| x as i64).sum::<i64>());
+    });
+    timer.measure("remove half", || {
+        values.iter().step_by(2).for_each(|x| {
+            set.remove(x);
+        })
+    });
+    timer.measure("drop", || drop(set));
+    timer
+}
+
+fn bench_vec<S: avl_tree_vec::Storage>(indices: &[usize], ranges: &[(usize, usize)]) -> Timer {
+    let mut timer = Timer::default();
+    let mut v = AvlTreeVecIn::<usize, S>::new();
+    timer.measure("insert", || {
+        indices.iter().for_each(|&i| v.insert(i, i));
+    });
+    timer.measure("get", || {
+        indices.iter().for_each(|&i| {
+            black_box(v.get(i));
+        })
+    });
+    timer.measure("reverse", || {
+        ranges.iter().for_each(|&(l, r)| v.reverse(l..r));
+    });
+    timer.measure("iter", || {
+        black_box(v.iter().sum::<usize>());
+    });
+    timer.measure("remove half", || {
+        indices.iter().rev().step_by(2).for_each(|&i| {
+            v.remove(i.min(v.len() - 1));
+        })
+    });
+    timer.measure("drop", || drop(v));
+    timer
+}
+
+fn main() {
+    let mut rng = rng();
+    let values = (0..N)
+        .map(|_| rng.random_range(-(N as i32)..N as i32))
+        .collect::<Vec<_>>();
+    let indices = (0..N).map(|i| rng.random_range(0..=i)).collect::<Vec<_>>();
+    let ranges = (0..N)
+        .map(|_| {
+            let l = rng.random_range(0..N);
+            let r = rng.random_range(l..=N);
+            (l, r)
+        })
+        .collect::<Vec<_>>();
+
+    compare(
+        "avl_tree_set",
+        || bench_set::<avl_tree_set::Boxed>(&values),
+        || bench_set::<avl_tree_set::Arena>(&values),
+    );
+    compare(
+        "avl_tree_set_i32",
+        || bench_set_i32::<avl_tree_set_i32::Boxed>(&values),
+        || bench_set_i32::<avl_tree_set_i32::Arena>(&values),
+    );
+    compare(
+        "avl_tree_vec",
+        || bench_vec::<avl_tree_vec::Boxed>(&indices, &ranges),
+        || bench_vec::<avl_tree_vec::Arena>(&indices, &ranges),
+    );
+}
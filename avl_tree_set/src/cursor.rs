@@ -0,0 +1,717 @@
+//! AvlTreeSetの任意の位置から前後に移動するカーソル
+//! カーソルは要素，または最後の要素と最初の要素の間にある「要素でない位置」(ghost)を指す
+//! 親へのポインタを持たないので根からの経路をスタックとして保持する
+
+use std::{error::Error, fmt::Display, marker::PhantomData, mem::take};
+
+use crate::{balance, node_len, Boxed, Link, Node, NodePool, NodePtr, Pool, Storage};
+
+/// カーソルの位置
+/// stackは根から現在のノードまでの経路で，ghostを指している場合は空
+struct Position<T> {
+    stack: Vec<NodePtr<T>>,
+    /// 現在のノードの昇順での位置(ghostの場合は要素数)
+    index: usize,
+}
+
+impl<T> Position<T> {
+    /// 昇順index番目の要素を指す位置(index >= Nの場合はghost)
+    fn new(root: Link<T>, index: usize) -> Self {
+        let len = node_len(root);
+        if index >= len {
+            return Self {
+                stack: vec![],
+                index: len,
+            };
+        }
+
+        let mut stack = vec![];
+        let mut cur = root;
+        let mut n = index;
+        while let Some(node) = cur {
+            stack.push(node);
+            let raw_node = unsafe { node.as_ref() };
+            let left_len = node_len(raw_node.left);
+            if n < left_len {
+                cur = raw_node.left;
+            } else if n > left_len {
+                cur = raw_node.right;
+                n -= left_len + 1;
+            } else {
+                break;
+            }
+        }
+        Self { stack, index }
+    }
+
+    fn current<'a>(&self) -> Option<&'a T> {
+        self.stack.last().map(|node| &unsafe { node.as_ref() }.key)
+    }
+
+    fn push_left(&mut self, mut node: Link<T>) {
+        while let Some(n) = node {
+            self.stack.push(n);
+            node = unsafe { n.as_ref() }.left;
+        }
+    }
+
+    fn push_right(&mut self, mut node: Link<T>) {
+        while let Some(n) = node {
+            self.stack.push(n);
+            node = unsafe { n.as_ref() }.right;
+        }
+    }
+
+    fn move_next(&mut self, root: Link<T>) {
+        let len = node_len(root);
+        let Some(&node) = self.stack.last() else {
+            self.push_left(root);
+            self.index = 0;
+            return;
+        };
+
+        self.index += 1;
+        if let Some(right) = unsafe { node.as_ref() }.right {
+            self.push_left(Some(right));
+            return;
+        }
+
+        // 左の子として辿ってきた祖先まで戻る
+        let mut child = self.stack.pop().unwrap();
+        while let Some(&parent) = self.stack.last() {
+            if unsafe { parent.as_ref() }.left == Some(child) {
+                return;
+            }
+            child = self.stack.pop().unwrap();
+        }
+        self.index = len;
+    }
+
+    fn move_prev(&mut self, root: Link<T>) {
+        let len = node_len(root);
+        let Some(&node) = self.stack.last() else {
+            self.push_right(root);
+            self.index = len.saturating_sub(1);
+            return;
+        };
+
+        if let Some(left) = unsafe { node.as_ref() }.left {
+            self.index -= 1;
+            self.push_right(Some(left));
+            return;
+        }
+
+        // 右の子として辿ってきた祖先まで戻る
+        let mut child = self.stack.pop().unwrap();
+        while let Some(&parent) = self.stack.last() {
+            if unsafe { parent.as_ref() }.right == Some(child) {
+                self.index -= 1;
+                return;
+            }
+            child = self.stack.pop().unwrap();
+        }
+        self.index = len;
+    }
+
+    fn peek_next<'a>(&self, root: Link<T>) -> Option<&'a T> {
+        let leftmost = |mut node: NodePtr<T>| {
+            while let Some(left) = unsafe { node.as_ref() }.left {
+                node = left;
+            }
+            &unsafe { node.as_ref() }.key
+        };
+
+        let Some(&node) = self.stack.last() else {
+            return root.map(leftmost);
+        };
+
+        if let Some(right) = unsafe { node.as_ref() }.right {
+            return Some(leftmost(right));
+        }
+
+        self.stack
+            .windows(2)
+            .rev()
+            .find(|w| unsafe { w[0].as_ref() }.left == Some(w[1]))
+            .map(|w| &unsafe { w[0].as_ref() }.key)
+    }
+
+    fn peek_prev<'a>(&self, root: Link<T>) -> Option<&'a T> {
+        let rightmost = |mut node: NodePtr<T>| {
+            while let Some(right) = unsafe { node.as_ref() }.right {
+                node = right;
+            }
+            &unsafe { node.as_ref() }.key
+        };
+
+        let Some(&node) = self.stack.last() else {
+            return root.map(rightmost);
+        };
+
+        if let Some(left) = unsafe { node.as_ref() }.left {
+            return Some(rightmost(left));
+        }
+
+        self.stack
+            .windows(2)
+            .rev()
+            .find(|w| unsafe { w[0].as_ref() }.right == Some(w[1]))
+            .map(|w| &unsafe { w[0].as_ref() }.key)
+    }
+}
+
+/// AvlTreeSetの読み取り専用のカーソル
+pub struct Cursor<'a, T> {
+    root: Link<T>,
+    pos: Position<T>,
+    phantom: PhantomData<&'a Node<T>>,
+}
+
+// NOTE: &AvlTreeSet<T>と同様
+unsafe impl<T: Sync> Send for Cursor<'_, T> {}
+unsafe impl<T: Sync> Sync for Cursor<'_, T> {}
+
+impl<'a, T> Cursor<'a, T> {
+    pub(crate) fn new(root: Link<T>, index: usize) -> Self {
+        Self {
+            root,
+            pos: Position::new(root, index),
+            phantom: PhantomData,
+        }
+    }
+
+    /// 現在の要素(ghostの場合はNone)
+    pub fn current(&self) -> Option<&'a T> {
+        self.pos.current()
+    }
+
+    /// 現在の要素の昇順での位置(ghostの場合はNone)
+    pub fn index(&self) -> Option<usize> {
+        self.current().map(|_| self.pos.index)
+    }
+
+    /// 次の要素に移動する
+    /// 最後の要素からはghostに，ghostからは最初の要素に移動する
+    /// NOTE: ならしO(1)
+    pub fn move_next(&mut self) {
+        self.pos.move_next(self.root);
+    }
+
+    /// 前の要素に移動する
+    /// 最初の要素からはghostに，ghostからは最後の要素に移動する
+    /// NOTE: ならしO(1)
+    pub fn move_prev(&mut self) {
+        self.pos.move_prev(self.root);
+    }
+
+    /// 次の要素
+    pub fn peek_next(&self) -> Option<&'a T> {
+        self.pos.peek_next(self.root)
+    }
+
+    /// 前の要素
+    pub fn peek_prev(&self) -> Option<&'a T> {
+        self.pos.peek_prev(self.root)
+    }
+}
+
+/// CursorMutへの挿入が集合の順序を崩す場合のエラー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnorderedKeyError;
+
+impl Display for UnorderedKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "key is not properly ordered relative to neighbors")
+    }
+}
+
+impl Error for UnorderedKeyError {}
+
+/// AvlTreeSetの要素の削除・挿入ができるカーソル
+pub struct CursorMut<'a, T, S: Storage = Boxed> {
+    root: &'a mut Link<T>,
+    pool: &'a mut NodePool<T, S>,
+    pos: Position<T>,
+}
+
+// NOTE: &mut AvlTreeSet<T>と同様
+unsafe impl<T: Send, S: Storage> Send for CursorMut<'_, T, S> {}
+unsafe impl<T: Sync, S: Storage> Sync for CursorMut<'_, T, S> {}
+
+impl<'a, T, S: Storage> CursorMut<'a, T, S> {
+    pub(crate) fn new(root: &'a mut Link<T>, pool: &'a mut NodePool<T, S>, index: usize) -> Self {
+        let pos = Position::new(*root, index);
+        Self { root, pool, pos }
+    }
+
+    /// 現在の要素(ghostの場合はNone)
+    pub fn current(&self) -> Option<&T> {
+        self.pos.current()
+    }
+
+    /// 現在の要素の昇順での位置(ghostの場合はNone)
+    pub fn index(&self) -> Option<usize> {
+        self.current().map(|_| self.pos.index)
+    }
+
+    /// 次の要素に移動する
+    /// 最後の要素からはghostに，ghostからは最初の要素に移動する
+    pub fn move_next(&mut self) {
+        self.pos.move_next(*self.root);
+    }
+
+    /// 前の要素に移動する
+    /// 最初の要素からはghostに，ghostからは最後の要素に移動する
+    pub fn move_prev(&mut self) {
+        self.pos.move_prev(*self.root);
+    }
+
+    /// 次の要素
+    pub fn peek_next(&self) -> Option<&T> {
+        self.pos.peek_next(*self.root)
+    }
+
+    /// 前の要素
+    pub fn peek_prev(&self) -> Option<&T> {
+        self.pos.peek_prev(*self.root)
+    }
+
+    /// 現在の要素以降の要素数(ghostの場合は0)
+    pub(crate) fn remaining(&self) -> usize {
+        node_len(*self.root) - self.pos.index
+    }
+
+    /// 同じ位置を指す読み取り専用のカーソル
+    pub fn as_cursor(&self) -> Cursor<'_, T> {
+        Cursor {
+            root: *self.root,
+            pos: Position {
+                stack: self.pos.stack.clone(),
+                index: self.pos.index,
+            },
+            phantom: PhantomData,
+        }
+    }
+
+    /// 現在の要素を削除して返し，カーソルを次の要素に移動する
+    /// ghostを指している場合は何もしない
+    /// NOTE: 保持している経路を使うので根から探索し直さない，O(log N)
+    pub fn remove_current(&mut self) -> Option<T> {
+        let mut stack = take(&mut self.pos.stack);
+        let mut sides = path_sides(&stack);
+        let mut node = stack.pop()?;
+        let raw_node = unsafe { node.as_mut() };
+
+        let (sub, path, rel) = match (raw_node.left.take(), raw_node.right.take()) {
+            (Some(left), Some(right)) => {
+                // 次の要素(右部分木の最小のノード)を削除するノードの位置に移す
+                let mut chain = vec![];
+                let mut next = right;
+                while let Some(left) = unsafe { next.as_ref() }.left {
+                    chain.push(next);
+                    next = left;
+                }
+                let raw_next = unsafe { next.as_mut() };
+                let sub = raw_next.right.take();
+                raw_next.left = Some(left);
+
+                stack.push(next);
+                sides.push(false);
+                sides.extend(chain.iter().map(|_| true));
+                stack.extend(chain);
+                // 次の要素はsubの直前にある
+                (sub, vec![], Some(-1))
+            }
+            (left, None) => {
+                // 次の要素はsubの直後にある
+                (left, vec![], Some(node_len(left) as isize))
+            }
+            (None, right) => {
+                // 次の要素はsubの最小のノード
+                let mut path = vec![];
+                let mut cur = right;
+                while let Some(n) = cur {
+                    path.push(n);
+                    cur = unsafe { n.as_ref() }.left;
+                }
+                path.reverse();
+                (right, path, Some(0))
+            }
+        };
+
+        self.pos.stack = fix_path(self.root, stack, sides, sub, path, rel);
+        Some(unsafe { self.pool.dealloc(node) }.key)
+    }
+
+    /// 現在の要素の直前にkeyを挿入する
+    /// ghostを指している場合は末尾に挿入する
+    /// 前の要素 < key < 現在の要素 を満たさない場合は挿入せずにエラーを返す
+    /// NOTE: 保持している経路を使うので根から探索し直さない，O(log N)
+    pub fn insert_before(&mut self, key: T) -> Result<(), UnorderedKeyError>
+    where
+        T: Ord,
+    {
+        if self.peek_prev().is_some_and(|prev| prev >= &key)
+            || self.current().is_some_and(|cur| &key >= cur)
+        {
+            return Err(UnorderedKeyError);
+        }
+
+        // 現在のノードの左部分木(ghostなら木全体)の最大のノードの右の子として挿入する
+        let mut stack = take(&mut self.pos.stack);
+        let mut sides = path_sides(&stack);
+        let (mut cur, rel) = match stack.last() {
+            Some(node) => {
+                sides.push(true);
+                // 現在の要素は新しいノードの直後にある
+                (unsafe { node.as_ref() }.left, Some(1))
+            }
+            None => (*self.root, None),
+        };
+        while let Some(n) = cur {
+            stack.push(n);
+            sides.push(false);
+            cur = unsafe { n.as_ref() }.right;
+        }
+
+        self.pos.stack = fix_path(
+            self.root,
+            stack,
+            sides,
+            Some(Node::new_in(key, self.pool)),
+            vec![],
+            rel,
+        );
+        self.pos.index += 1;
+        Ok(())
+    }
+
+    /// 現在の要素の直後にkeyを挿入する
+    /// ghostを指している場合は先頭に挿入する
+    /// 現在の要素 < key < 次の要素 を満たさない場合は挿入せずにエラーを返す
+    /// NOTE: 保持している経路を使うので根から探索し直さない，O(log N)
+    pub fn insert_after(&mut self, key: T) -> Result<(), UnorderedKeyError>
+    where
+        T: Ord,
+    {
+        if self.current().is_some_and(|cur| cur >= &key)
+            || self.peek_next().is_some_and(|next| &key >= next)
+        {
+            return Err(UnorderedKeyError);
+        }
+
+        // 現在のノードの右部分木(ghostなら木全体)の最小のノードの左の子として挿入する
+        let mut stack = take(&mut self.pos.stack);
+        let mut sides = path_sides(&stack);
+        let (mut cur, rel) = match stack.last() {
+            Some(node) => {
+                sides.push(false);
+                // 現在の要素は新しいノードの直前にある
+                (unsafe { node.as_ref() }.right, Some(-1))
+            }
+            None => {
+                self.pos.index += 1;
+                (*self.root, None)
+            }
+        };
+        while let Some(n) = cur {
+            stack.push(n);
+            sides.push(true);
+            cur = unsafe { n.as_ref() }.left;
+        }
+
+        self.pos.stack = fix_path(
+            self.root,
+            stack,
+            sides,
+            Some(Node::new_in(key, self.pool)),
+            vec![],
+            rel,
+        );
+        Ok(())
+    }
+}
+
+/// 経路上の各ノードについて，経路上の次のノードが左の子かどうか
+fn path_sides<T>(stack: &[NodePtr<T>]) -> Vec<bool> {
+    stack
+        .windows(2)
+        .map(|w| unsafe { w[0].as_ref() }.left == Some(w[1]))
+        .collect()
+}
+
+/// stackの最後のノードの子(向きはsidesの最後)をsubに付け替えて，経路を遡りながら平衡を取り直す
+/// sidesはstackの各ノードについて経路上の子が左の子かどうか
+/// pathはsubの根から注目するノードまでの経路を逆順にしたもの
+/// relはsubの最初の要素から数えた注目するノードの位置(ghostならNone)
+/// 平衡を取り直した後の根から注目するノードまでの経路を返す
+fn fix_path<T>(
+    root: &mut Link<T>,
+    stack: Vec<NodePtr<T>>,
+    sides: Vec<bool>,
+    mut sub: Link<T>,
+    mut path: Vec<NodePtr<T>>,
+    mut rel: Option<isize>,
+) -> Vec<NodePtr<T>> {
+    for (mut node, is_left) in stack.into_iter().zip(sides).rev() {
+        let raw_node = unsafe { node.as_mut() };
+        if is_left {
+            raw_node.left = sub;
+        } else {
+            raw_node.right = sub;
+            rel = rel.map(|rel| rel + node_len(raw_node.left) as isize + 1);
+        }
+
+        let mut link = Some(node);
+        balance(&mut link);
+        sub = link;
+        path = repair_path(sub.unwrap(), rel, path);
+    }
+    *root = sub;
+    path.reverse();
+    path
+}
+
+/// 回転後の部分木の根からrel番目のノードまでの経路を逆順で求める
+/// 回転で付け替わるのは根から2段下までなので，回転前の経路の3番目以降のノードはそのまま使える
+/// NOTE: O(1)
+fn repair_path<T>(
+    root: NodePtr<T>,
+    rel: Option<isize>,
+    mut path: Vec<NodePtr<T>>,
+) -> Vec<NodePtr<T>> {
+    let Some(mut n) = rel
+        .and_then(|rel| usize::try_from(rel).ok())
+        .filter(|&rel| rel < node_len(Some(root)))
+    else {
+        return vec![];
+    };
+
+    let reusable = path.len().checked_sub(3).map(|i| path[i]);
+    let mut top = vec![];
+    let mut cur = root;
+    loop {
+        if Some(cur) == reusable {
+            path.truncate(path.len() - 2);
+            break;
+        }
+        top.push(cur);
+        let raw_node = unsafe { cur.as_ref() };
+        let left_len = node_len(raw_node.left);
+        if n < left_len {
+            cur = raw_node.left.unwrap();
+        } else if n > left_len {
+            cur = raw_node.right.unwrap();
+            n -= left_len + 1;
+        } else {
+            path.clear();
+            break;
+        }
+    }
+    path.extend(top.into_iter().rev());
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{node_height, node_len, AvlTreeSet, Link};
+
+    use super::UnorderedKeyError;
+
+    #[test]
+    fn test_avl_tree_set_cursor_move() {
+        let st = AvlTreeSet::from([1, 3, 5, 7, 9]);
+
+        let mut cur = st.lower_bound_cursor(&4);
+        assert_eq!(cur.current(), Some(&5));
+        assert_eq!(cur.index(), Some(2));
+        assert_eq!(cur.peek_prev(), Some(&3));
+        assert_eq!(cur.peek_next(), Some(&7));
+        cur.move_next();
+        assert_eq!(cur.current(), Some(&7));
+        cur.move_next();
+        cur.move_next();
+        assert_eq!(cur.current(), None);
+        assert_eq!(cur.index(), None);
+        assert_eq!(cur.peek_next(), Some(&1));
+        assert_eq!(cur.peek_prev(), Some(&9));
+        cur.move_next();
+        assert_eq!(cur.current(), Some(&1));
+        assert_eq!(cur.index(), Some(0));
+        cur.move_prev();
+        assert_eq!(cur.current(), None);
+        cur.move_prev();
+        assert_eq!(cur.current(), Some(&9));
+        assert_eq!(cur.index(), Some(4));
+
+        let cur = st.lower_bound_cursor(&5);
+        assert_eq!(cur.current(), Some(&5));
+        let cur = st.upper_bound_cursor(&5);
+        assert_eq!(cur.current(), Some(&7));
+        let cur = st.upper_bound_cursor(&9);
+        assert_eq!(cur.current(), None);
+
+        let empty = AvlTreeSet::<i32>::new();
+        let mut cur = empty.lower_bound_cursor(&0);
+        assert_eq!(cur.current(), None);
+        cur.move_next();
+        assert_eq!(cur.current(), None);
+        assert_eq!(cur.peek_prev(), None);
+    }
+
+    #[test]
+    fn test_avl_tree_set_cursor_walk() {
+        let st = AvlTreeSet::from_iter(0..100);
+
+        let mut cur = st.lower_bound_cursor(&0);
+        for i in 0..100 {
+            assert_eq!(cur.current(), Some(&i));
+            assert_eq!(cur.index(), Some(i as usize));
+            assert_eq!(cur.peek_next(), (i < 99).then_some(&(i + 1)));
+            assert_eq!(cur.peek_prev(), (i > 0).then_some(&(i - 1)));
+            cur.move_next();
+        }
+        assert_eq!(cur.current(), None);
+        for i in (0..100).rev() {
+            cur.move_prev();
+            assert_eq!(cur.current(), Some(&i));
+        }
+    }
+
+    #[test]
+    fn test_avl_tree_set_cursor_mut() {
+        let mut st = AvlTreeSet::from([10, 20, 30, 40]);
+
+        let mut cur = st.lower_bound_cursor_mut(&20);
+        assert_eq!(cur.remove_current(), Some(20));
+        assert_eq!(cur.current(), Some(&30));
+        assert_eq!(cur.insert_before(25), Ok(()));
+        assert_eq!(cur.current(), Some(&30));
+        assert_eq!(cur.peek_prev(), Some(&25));
+        assert_eq!(cur.insert_before(5), Err(UnorderedKeyError));
+        assert_eq!(cur.insert_before(30), Err(UnorderedKeyError));
+        assert_eq!(cur.insert_after(35), Ok(()));
+        assert_eq!(cur.insert_after(50), Err(UnorderedKeyError));
+        assert_eq!(cur.current(), Some(&30));
+        assert_eq!(cur.peek_next(), Some(&35));
+        assert_eq!(cur.as_cursor().index(), Some(2));
+        assert!(st.iter().copied().eq([10, 25, 30, 35, 40]));
+
+        let mut cur = st.upper_bound_cursor_mut(&40);
+        assert_eq!(cur.current(), None);
+        assert_eq!(cur.remove_current(), None);
+        assert_eq!(cur.insert_before(50), Ok(()));
+        assert_eq!(cur.insert_after(0), Ok(()));
+        assert_eq!(cur.insert_after(1), Err(UnorderedKeyError));
+        cur.move_prev();
+        assert_eq!(cur.remove_current(), Some(50));
+        assert_eq!(cur.current(), None);
+        assert!(st.iter().copied().eq([0, 10, 25, 30, 35, 40]));
+        assert_eq!(st.len(), 6);
+    }
+
+    #[test]
+    fn test_avl_tree_set_cursor_mut_balanced() {
+        fn is_balanced<T>(node: Link<T>) -> bool {
+            node.is_none_or(|node| {
+                let node = unsafe { node.as_ref() };
+                (node_height(node.left) - node_height(node.right)).abs() <= 1
+                    && node.height == node_height(node.left).max(node_height(node.right)) + 1
+                    && node.len == node_len(node.left) + node_len(node.right) + 1
+                    && is_balanced(node.left)
+                    && is_balanced(node.right)
+            })
+        }
+
+        let mut st = AvlTreeSet::new();
+        let mut cur = st.lower_bound_cursor_mut(&0);
+        for i in 0..200 {
+            cur.insert_before(i).unwrap();
+            assert!(is_balanced(*cur.root));
+        }
+
+        let mut cur = st.lower_bound_cursor_mut(&50);
+        for i in 50..150 {
+            assert_eq!(cur.remove_current(), Some(i));
+            assert_eq!(cur.current(), Some(&(i + 1)));
+            assert!(is_balanced(*cur.root));
+        }
+        for i in 50..150 {
+            cur.insert_before(i).unwrap();
+            assert_eq!(cur.current(), Some(&150));
+            assert_eq!(cur.peek_prev(), Some(&i));
+            assert!(is_balanced(*cur.root));
+        }
+
+        let mut cur = st.upper_bound_cursor_mut(&200);
+        for i in 1..=100 {
+            cur.insert_after(-i).unwrap();
+            assert_eq!(cur.index(), None);
+            assert_eq!(cur.peek_next(), Some(&-i));
+            assert!(is_balanced(*cur.root));
+        }
+        assert!(st.iter().copied().eq(-100..200));
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_cursor_mut_random() {
+        use rand::{thread_rng, Rng};
+
+        let mut rng = thread_rng();
+
+        let mut st = AvlTreeSet::from_iter((0..200).map(|x| x * 4));
+        let mut v = (0..200).map(|x| x * 4).collect::<Vec<i32>>();
+        let mut cur = st.lower_bound_cursor_mut(&400);
+        let mut index = 100;
+        for _ in 0..2000 {
+            // 0: move_next
+            // 1: move_prev
+            // 2: remove_current
+            // 3: insert_before
+            // 4: insert_after
+            match rng.gen_range(0..5) {
+                0 => {
+                    cur.move_next();
+                    index = (index + 1) % (v.len() + 1);
+                }
+                1 => {
+                    cur.move_prev();
+                    index = (index + v.len()) % (v.len() + 1);
+                }
+                2 => {
+                    assert_eq!(
+                        cur.remove_current(),
+                        (index < v.len()).then(|| v.remove(index))
+                    );
+                }
+                3 => {
+                    let x = rng.gen_range(-10..810);
+                    let ok = (index == 0 || v[index - 1] < x) && (index == v.len() || x < v[index]);
+                    assert_eq!(cur.insert_before(x).is_ok(), ok);
+                    if ok {
+                        v.insert(index, x);
+                        index += 1;
+                    }
+                }
+                4 => {
+                    let x = rng.gen_range(-10..810);
+                    let next = if index == v.len() { 0 } else { index + 1 };
+                    let ok = (index == v.len() || v[index] < x) && (next == v.len() || x < v[next]);
+                    assert_eq!(cur.insert_after(x).is_ok(), ok);
+                    if ok {
+                        v.insert(next, x);
+                        if index == v.len() - 1 {
+                            index += 1;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            assert_eq!(cur.current(), v.get(index));
+            assert_eq!(cur.index(), (index < v.len()).then_some(index));
+        }
+        assert!(st.iter().eq(v.iter()));
+    }
+}
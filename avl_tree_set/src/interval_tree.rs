@@ -0,0 +1,420 @@
+//! AVL木による区間木の実装
+//! 半開区間を左端・右端の辞書順に並べ，各ノードは部分木の空でない区間の右端の最大値を持つ
+//! 空の区間(start >= end)も保持できるが，点を含まないのでどの区間とも共通部分を持たない
+
+use std::{
+    fmt::Debug,
+    marker::PhantomData,
+    ops::{Bound, Range},
+    ptr::NonNull,
+};
+
+use crate::{
+    clone_tree, clone_tree_from, free, merge, merge_with_root, node_len, remove_nth, split_by,
+    traverse_postorder, Augment, BoxPool, Link, Node, NodeRange,
+};
+
+/// 部分木の空でない区間の右端の最大値(空でない区間がなければNone)
+struct MaxEnd<K>(Option<K>);
+
+impl<K: Ord + Clone, V> Augment<(Range<K>, V)> for MaxEnd<K> {
+    fn fetch(key: &(Range<K>, V), left: Option<&Self>, right: Option<&Self>) -> Self {
+        let res = [left, right]
+            .into_iter()
+            .flatten()
+            .filter_map(|x| x.0.as_ref())
+            .chain((!key.0.is_empty()).then_some(&key.0.end))
+            .max();
+        MaxEnd(res.cloned())
+    }
+}
+
+impl<K: Clone> Clone for MaxEnd<K> {
+    fn clone(&self) -> Self {
+        MaxEnd(self.0.clone())
+    }
+}
+
+type IntervalNode<K, V> = Node<(Range<K>, V), MaxEnd<K>>;
+type IntervalLink<K, V> = Link<(Range<K>, V), MaxEnd<K>>;
+
+/// 部分木に右端がxより大きい空でない区間が存在するか
+#[inline]
+fn exists_end_after<K: Ord, V>(node: IntervalLink<K, V>, x: &K) -> bool {
+    node.is_some_and(|node| {
+        unsafe { node.as_ref() }
+            .aug
+            .0
+            .as_ref()
+            .is_some_and(|end| x < end)
+    })
+}
+
+/// AVL木による区間木の実装
+/// 半開区間`start..end`と値の組を保持する
+/// 等しい区間を複数保持でき，それらは挿入順に並ぶ
+/// 空の区間は保持・削除できるが，overlapping・stabbing・any_overlapでは共通部分を持たないものとして扱う
+pub struct AvlIntervalTree<K, V> {
+    root: IntervalLink<K, V>,
+    phantom: PhantomData<Box<IntervalNode<K, V>>>,
+}
+
+unsafe impl<K: Send, V: Send> Send for AvlIntervalTree<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for AvlIntervalTree<K, V> {}
+
+impl<K, V> AvlIntervalTree<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// 区間の数
+    pub fn len(&self) -> usize {
+        node_len(self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 区間を左端・右端の辞書順に返すイテレータ
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter(NodeRange::new(self.root, |_| true, |_| true))
+    }
+}
+
+impl<K: Ord + Clone, V> AvlIntervalTree<K, V> {
+    /// rangeより前にある区間からなる木，rangeと等しい区間からなる木，rangeより後にある区間からなる木に分割する
+    fn split3(&mut self, range: &Range<K>) -> [IntervalLink<K, V>; 3] {
+        let key = (&range.start, &range.end);
+        let (less, rest) = split_by(self.root.take(), &mut |x: &(Range<K>, V)| {
+            (&x.0.start, &x.0.end) < key
+        });
+        let (equal, greater) =
+            split_by(rest, &mut |x: &(Range<K>, V)| (&x.0.start, &x.0.end) <= key);
+        [less, equal, greater]
+    }
+
+    /// 区間rangeと値valueを挿入する
+    /// NOTE: O(log N)
+    pub fn insert(&mut self, range: Range<K>, value: V) {
+        let [less, equal, greater] = self.split3(&range);
+        let new_node = Node::new((range, value));
+        self.root = merge(less, merge_with_root(equal, new_node, greater));
+    }
+
+    /// rangeと等しい区間のうち最初に挿入されたものを削除して，その値を返す
+    /// NOTE: O(log N)
+    pub fn remove(&mut self, range: &Range<K>) -> Option<V> {
+        let [less, mut equal, greater] = self.split3(range);
+        let res = equal.is_some().then(|| {
+            let node = remove_nth(&mut equal, 0);
+            let boxed = unsafe { Box::from_raw(node.as_ptr()) };
+            boxed.key.1
+        });
+        self.root = merge(merge(less, equal), greater);
+        res
+    }
+
+    /// queryと共通部分を持つ区間を左端・右端の辞書順に返すイテレータ
+    /// queryや保持している区間が空の場合は共通部分を持たない
+    /// NOTE: 該当する区間の数Kに対してO(min(N, (K + 1) log N))
+    pub fn overlapping(&self, query: Range<K>) -> Overlapping<'_, K, V> {
+        let root = if query.is_empty() { None } else { self.root };
+        Overlapping::new(root, query.start, Bound::Excluded(query.end))
+    }
+
+    /// pointを含む区間を左端・右端の辞書順に返すイテレータ
+    /// NOTE: 該当する区間の数Kに対してO(min(N, (K + 1) log N))
+    pub fn stabbing(&self, point: &K) -> Overlapping<'_, K, V> {
+        Overlapping::new(self.root, point.clone(), Bound::Included(point.clone()))
+    }
+
+    /// queryと共通部分を持つ区間が存在するか
+    /// queryや保持している区間が空の場合は共通部分を持たない
+    /// NOTE: O(log N)
+    pub fn any_overlap(&self, query: &Range<K>) -> bool {
+        if query.is_empty() {
+            return false;
+        }
+        let mut cur = self.root;
+        while let Some(node) = cur.map(|node| unsafe { node.as_ref() }) {
+            let range = &node.key.0;
+            if !range.is_empty() && range.start < query.end && query.start < range.end {
+                return true;
+            }
+            // 左部分木に右端がquery.startより大きい区間があれば，
+            // 左部分木で共通部分を持たない場合は右部分木でも共通部分を持たない
+            cur = if exists_end_after(node.left, &query.start) {
+                node.left
+            } else {
+                node.right
+            };
+        }
+        false
+    }
+}
+
+impl<K, V> Drop for AvlIntervalTree<K, V> {
+    fn drop(&mut self) {
+        traverse_postorder(self.root, free);
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for AvlIntervalTree<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            root: clone_tree(self.root, &mut BoxPool),
+            phantom: PhantomData,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        clone_tree_from(&mut self.root, source.root, &mut BoxPool);
+    }
+}
+
+impl<K, V> Default for AvlIntervalTree<K, V> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for AvlIntervalTree<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a AvlIntervalTree<K, V> {
+    type IntoIter = Iter<'a, K, V>;
+    type Item = (&'a Range<K>, &'a V);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord + Clone, V> Extend<(Range<K>, V)> for AvlIntervalTree<K, V> {
+    fn extend<I: IntoIterator<Item = (Range<K>, V)>>(&mut self, iter: I) {
+        iter.into_iter()
+            .for_each(|(range, value)| self.insert(range, value));
+    }
+}
+
+impl<K: Ord + Clone, V> FromIterator<(Range<K>, V)> for AvlIntervalTree<K, V> {
+    fn from_iter<I: IntoIterator<Item = (Range<K>, V)>>(iter: I) -> Self {
+        let mut res = Self::new();
+        res.extend(iter);
+        res
+    }
+}
+
+/// 区間を左端・右端の辞書順に返すイテレータ
+pub struct Iter<'a, K, V>(NodeRange<'a, (Range<K>, V), MaxEnd<K>>);
+
+impl<'a, K: 'a, V: 'a> Iterator for Iter<'a, K, V> {
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = unsafe { self.0.next()?.as_ref() };
+        Some((&node.key.0, &node.key.1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<'a, K: 'a, V: 'a> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = unsafe { self.0.next_back()?.as_ref() };
+        Some((&node.key.0, &node.key.1))
+    }
+}
+
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for Iter<'a, K, V> {}
+
+/// 右端がlowerより大きく，左端がupperで指定される上限を満たす空でない区間を返すイテレータ
+/// 右端の最大値がlower以下の部分木と，左端が上限を満たさないノードより後ろは探索しない
+pub struct Overlapping<'a, K, V> {
+    stack: Vec<NonNull<IntervalNode<K, V>>>,
+    lower: K,
+    upper: Bound<K>,
+    phantom: PhantomData<&'a IntervalNode<K, V>>,
+}
+
+// NOTE: 木は共有参照として扱い，lowerとupperは所有する
+unsafe impl<K: Send + Sync, V: Sync> Send for Overlapping<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for Overlapping<'_, K, V> {}
+
+impl<'a, K: Ord, V> Overlapping<'a, K, V> {
+    fn new(root: IntervalLink<K, V>, lower: K, upper: Bound<K>) -> Self {
+        let mut res = Self {
+            stack: vec![],
+            lower,
+            upper,
+            phantom: PhantomData,
+        };
+        res.push_left(root);
+        res
+    }
+
+    /// nodeから左の子を辿ってスタックに積む
+    fn push_left(&mut self, mut node: IntervalLink<K, V>) {
+        while let Some(n) = node {
+            if !exists_end_after(node, &self.lower) {
+                break;
+            }
+            self.stack.push(n);
+            node = unsafe { n.as_ref() }.left;
+        }
+    }
+}
+
+impl<'a, K: 'a + Ord, V: 'a> Iterator for Overlapping<'a, K, V> {
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            let raw_node = unsafe { node.as_ref() };
+            let range = &raw_node.key.0;
+            let before_upper = match &self.upper {
+                Bound::Included(upper) => range.start <= *upper,
+                Bound::Excluded(upper) => range.start < *upper,
+                Bound::Unbounded => true,
+            };
+            if !before_upper {
+                // 以降の区間はすべて左端が上限を満たさない
+                self.stack.clear();
+                return None;
+            }
+            self.push_left(raw_node.right);
+            if !range.is_empty() && self.lower < range.end {
+                return Some((range, &raw_node.key.1));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AvlIntervalTree;
+
+    #[test]
+    fn test_avl_interval_tree_overlapping() {
+        let mut tree = AvlIntervalTree::new();
+        tree.insert(1..5, "a");
+        tree.insert(3..4, "b");
+        tree.insert(6..9, "c");
+        tree.insert(2..10, "d");
+        tree.insert(8..9, "e");
+        assert_eq!(tree.len(), 5);
+        assert!(tree.iter().map(|(_, &v)| v).eq(["a", "d", "b", "c", "e"]));
+
+        assert!(tree.overlapping(4..7).map(|(_, &v)| v).eq(["a", "d", "c"]));
+        assert!(tree.overlapping(5..6).map(|(_, &v)| v).eq(["d"]));
+        assert!(tree.overlapping(10..12).next().is_none());
+        assert!(tree.stabbing(&3).map(|(_, &v)| v).eq(["a", "d", "b"]));
+        assert!(tree.stabbing(&9).map(|(_, &v)| v).eq(["d"]));
+        assert!(tree.stabbing(&0).next().is_none());
+
+        assert!(tree.any_overlap(&(9..11)));
+        assert!(!tree.any_overlap(&(10..11)));
+        assert!(!tree.any_overlap(&(-3..1)));
+
+        assert_eq!(tree.remove(&(2..10)), Some("d"));
+        assert_eq!(tree.remove(&(2..10)), None);
+        assert!(!tree.any_overlap(&(9..11)));
+        assert!(tree.stabbing(&8).map(|(_, &v)| v).eq(["c", "e"]));
+    }
+
+    #[test]
+    fn test_avl_interval_tree_empty_interval() {
+        let mut tree = AvlIntervalTree::new();
+        tree.insert(3..8, "a");
+        tree.insert(5..5, "b");
+        tree.insert(6..7, "c");
+        assert_eq!(tree.len(), 3);
+
+        // 空の区間はどの区間とも共通部分を持たない
+        assert!(tree.overlapping(5..5).next().is_none());
+        assert!(!tree.any_overlap(&(5..5)));
+        assert!(tree.overlapping(3..8).map(|(_, &v)| v).eq(["a", "c"]));
+        assert!(tree.stabbing(&5).map(|(_, &v)| v).eq(["a"]));
+
+        assert_eq!(tree.remove(&(3..8)), Some("a"));
+        assert!(!tree.any_overlap(&(4..6)));
+        assert!(tree.any_overlap(&(4..7)));
+        assert_eq!(tree.remove(&(5..5)), Some("b"));
+        assert!(tree.iter().map(|(_, &v)| v).eq(["c"]));
+    }
+
+    #[test]
+    fn test_avl_interval_tree_duplicates() {
+        let mut tree = AvlIntervalTree::from_iter([(1..3, 0), (1..3, 1), (0..2, 2), (1..3, 3)]);
+        assert!(tree.stabbing(&1).map(|(_, &v)| v).eq([2, 0, 1, 3]));
+        assert_eq!(tree.remove(&(1..3)), Some(0));
+        assert_eq!(tree.remove(&(1..3)), Some(1));
+        assert!(tree.iter().map(|(_, &v)| v).eq([2, 3]));
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_interval_tree_random() {
+        use rand::{thread_rng, Rng};
+
+        let mut rng = thread_rng();
+
+        for _ in 0..5 {
+            let mut tree = AvlIntervalTree::new();
+            // 左端・右端の辞書順，等しい区間は挿入順に並べたもの
+            let mut v: Vec<(std::ops::Range<i32>, usize)> = vec![];
+            for i in 0..1000 {
+                // 0: insert
+                // 1: remove
+                // 2: overlapping
+                // 3: stabbing
+                // 4: any_overlap
+                let t = rng.gen_range(0..5);
+                let l = rng.gen_range(0..100);
+                let r = rng.gen_range(l..=(l + 20));
+                match t {
+                    0 => {
+                        tree.insert(l..r, i);
+                        let pos = v.partition_point(|(x, _)| (x.start, x.end) <= (l, r));
+                        v.insert(pos, (l..r, i));
+                    }
+                    1 => {
+                        let pos = v.iter().position(|(x, _)| *x == (l..r));
+                        assert_eq!(tree.remove(&(l..r)), pos.map(|pos| v.remove(pos).1));
+                    }
+                    2 => {
+                        // 両方の区間に含まれる点が存在するか
+                        let expected = v.iter().filter(|(x, _)| x.start.max(l) < x.end.min(r));
+                        assert!(tree.overlapping(l..r).eq(expected.map(|(x, y)| (x, y))));
+                    }
+                    3 => {
+                        let expected = v.iter().filter(|(x, _)| x.contains(&l));
+                        assert!(tree.stabbing(&l).eq(expected.map(|(x, y)| (x, y))));
+                    }
+                    4 => {
+                        let expected = v.iter().any(|(x, _)| x.start.max(l) < x.end.min(r));
+                        assert_eq!(tree.any_overlap(&(l..r)), expected);
+                    }
+                    _ => {}
+                }
+                assert_eq!(tree.len(), v.len());
+            }
+            assert!(tree.clone().iter().eq(v.iter().map(|(x, y)| (x, y))));
+        }
+    }
+}
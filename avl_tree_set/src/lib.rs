@@ -1,69 +1,117 @@
 //! AVL木によるordered setの実装
-//! # NOTE
-//! - rangeの計算量が悪い
-//! - (全体のk番目は取得できるが)指定した範囲のk番目を取得できない(逆も然り)
 
 use std::{
     borrow::Borrow,
     cmp::Ordering,
     fmt::{Debug, Display},
     hash::Hash,
-    mem::{swap, take},
-    ops::{Bound, RangeBounds, RangeFull},
+    marker::PhantomData,
+    mem::{needs_drop, swap, take},
+    ops::{Bound, RangeBounds},
     ptr::NonNull,
 };
 
-#[derive(Clone)]
-struct Node<T> {
+pub mod cursor;
+pub mod interval_tree;
+pub mod map;
+pub mod monoid_map;
+pub mod multiset;
+pub mod persistent;
+pub mod set_by;
+
+pub use avl_tree_storage::{Arena, Boxed, Storage};
+pub use cursor::{Cursor, CursorMut};
+pub use interval_tree::AvlIntervalTree;
+pub use map::AvlTreeMap;
+pub use monoid_map::{AvlTreeMonoidMap, Monoid};
+pub use multiset::AvlTreeMultiSet;
+pub use persistent::PersistentAvlTreeSet;
+pub use set_by::AvlTreeSetBy;
+
+use avl_tree_storage::{BoxPool, Pool};
+
+/// 部分木から計算してノードに持たせる値
+/// 回転などで部分木が更新されるたびにNode::fetchで再計算される
+trait Augment<T> {
+    fn fetch(key: &T, left: Option<&Self>, right: Option<&Self>) -> Self;
+}
+
+impl<T> Augment<T> for () {
+    #[inline]
+    fn fetch(_: &T, _: Option<&Self>, _: Option<&Self>) -> Self {}
+}
+
+struct Node<T, A = ()> {
     key: T,
     len: usize,
     height: i32,
-    left: Link<T>,
-    right: Link<T>,
+    aug: A,
+    left: Link<T, A>,
+    right: Link<T, A>,
 }
 
-type NodePtr<T> = NonNull<Node<T>>;
-type Link<T> = Option<NodePtr<T>>;
+type NodePtr<T, A = ()> = NonNull<Node<T, A>>;
+type Link<T, A = ()> = Option<NodePtr<T, A>>;
+
+impl<T, A: Augment<T>> Node<T, A> {
+    fn new(key: T) -> NodePtr<T, A> {
+        Self::new_in(key, &mut BoxPool)
+    }
 
-impl<T> Node<T> {
-    fn new(key: T) -> NodePtr<T> {
-        let node = Self {
+    /// poolから確保したノード
+    fn new_in(key: T, pool: &mut impl Pool<Self>) -> NodePtr<T, A> {
+        pool.alloc(Self {
+            aug: A::fetch(&key, None, None),
             key,
             len: 1,
             height: 1,
             left: None,
             right: None,
-        };
-        NonNull::from(Box::leak(Box::new(node)))
+        })
     }
 
     /// 部分木が更新された場合に呼ぶ
-    /// 部分木の長さと高さを再計算する
+    /// 部分木の長さと高さ，部分木から計算される値を再計算する
     #[inline]
     fn fetch(&mut self) {
         self.len = node_len(self.left) + node_len(self.right) + 1;
         self.height = node_height(self.left).max(node_height(self.right)) + 1;
+        self.aug = A::fetch(
+            &self.key,
+            self.left.map(|node| &unsafe { node.as_ref() }.aug),
+            self.right.map(|node| &unsafe { node.as_ref() }.aug),
+        );
     }
 }
 
 #[inline]
-fn free<T>(node: NodePtr<T>) {
+fn free<T, A>(node: NodePtr<T, A>) {
     unsafe { drop(Box::from_raw(node.as_ptr())) };
 }
 
+/// rootを根とする木の全ノードを解放する
+/// poolが領域をまとめて解放できる場合は要素のDropだけを行う
+fn free_tree<T, A, P: Pool<Node<T, A>>>(root: Link<T, A>, pool: &mut P) {
+    if P::FREE_EACH {
+        traverse_postorder(root, |node| drop(unsafe { pool.dealloc(node) }));
+    } else if needs_drop::<Node<T, A>>() {
+        traverse_postorder(root, |node| unsafe { node.as_ptr().drop_in_place() });
+    }
+}
+
 #[inline]
-fn node_len<T>(node: Link<T>) -> usize {
+fn node_len<T, A>(node: Link<T, A>) -> usize {
     node.map_or(0, |node| unsafe { node.as_ref() }.len)
 }
 
 #[inline]
-fn node_height<T>(node: Link<T>) -> i32 {
+fn node_height<T, A>(node: Link<T, A>) -> i32 {
     node.map_or(0, |node| unsafe { node.as_ref() }.height)
 }
 
 /// rootを根とした部分木を右回転させる
 /// (左の子が存在している場合のみ呼び出す)
-fn rotate_right<T>(root: &mut Link<T>) {
+fn rotate_right<T, A: Augment<T>>(root: &mut Link<T, A>) {
     *root = {
         unsafe {
             let mut root = root.unwrap();
@@ -79,7 +127,7 @@ fn rotate_right<T>(root: &mut Link<T>) {
 
 /// rootを根とした部分木を左回転させる
 /// (右の子が存在する場合のみ呼び出す)
-fn rotate_left<T>(root: &mut Link<T>) {
+fn rotate_left<T, A: Augment<T>>(root: &mut Link<T, A>) {
     *root = {
         unsafe {
             let mut root = root.unwrap();
@@ -94,11 +142,11 @@ fn rotate_left<T>(root: &mut Link<T>) {
 }
 
 /// rootを根とした部分木を平衡させる
-fn balance<T>(root: &mut Link<T>) {
+fn balance<T, A: Augment<T>>(root: &mut Link<T, A>) {
     /// 左部分木と右部分木の高さの差
     /// 左部分木の高さ - 右部分木の高さ
     #[inline]
-    fn diff_height<T>(node: Link<T>) -> i32 {
+    fn diff_height<T, A>(node: Link<T, A>) -> i32 {
         node.map_or(0, |node| {
             let node = unsafe { node.as_ref() };
             node_height(node.left) - node_height(node.right)
@@ -135,17 +183,17 @@ fn balance<T>(root: &mut Link<T>) {
 }
 
 #[allow(unused)]
-fn traverse<T>(
-    node: Link<T>,
-    mut preorder_f: impl FnMut(NodePtr<T>),
-    mut inorder_f: impl FnMut(NodePtr<T>),
-    mut postorder_f: impl FnMut(NodePtr<T>),
+fn traverse<T, A>(
+    node: Link<T, A>,
+    mut preorder_f: impl FnMut(NodePtr<T, A>),
+    mut inorder_f: impl FnMut(NodePtr<T, A>),
+    mut postorder_f: impl FnMut(NodePtr<T, A>),
 ) {
-    fn traverse<T>(
-        node: Link<T>,
-        preorder_f: &mut impl FnMut(NodePtr<T>),
-        inorder_f: &mut impl FnMut(NodePtr<T>),
-        postorder_f: &mut impl FnMut(NodePtr<T>),
+    fn traverse<T, A>(
+        node: Link<T, A>,
+        preorder_f: &mut impl FnMut(NodePtr<T, A>),
+        inorder_f: &mut impl FnMut(NodePtr<T, A>),
+        postorder_f: &mut impl FnMut(NodePtr<T, A>),
     ) {
         if let Some(node) = node {
             let left = unsafe { node.as_ref() }.left;
@@ -163,65 +211,458 @@ fn traverse<T>(
 
 #[allow(unused)]
 #[inline]
-fn traverse_preorder<T>(node: Link<T>, f: impl FnMut(NodePtr<T>)) {
+fn traverse_preorder<T, A>(node: Link<T, A>, f: impl FnMut(NodePtr<T, A>)) {
     traverse(node, f, |_| {}, |_| {});
 }
 
 #[allow(unused)]
 #[inline]
-fn traverse_inorder<T>(node: Link<T>, f: impl FnMut(NodePtr<T>)) {
+fn traverse_inorder<T, A>(node: Link<T, A>, f: impl FnMut(NodePtr<T, A>)) {
     traverse(node, |_| {}, f, |_| {});
 }
 
 #[allow(unused)]
 #[inline]
-fn traverse_postorder<T>(node: Link<T>, f: impl FnMut(NodePtr<T>)) {
+fn traverse_postorder<T, A>(node: Link<T, A>, f: impl FnMut(NodePtr<T, A>)) {
     traverse(node, |_| {}, |_| {}, f);
 }
 
+/// 木の形をそのままにして複製する
+/// NOTE: O(N)
+fn clone_tree<T: Clone, A: Clone>(
+    node: Link<T, A>,
+    pool: &mut impl Pool<Node<T, A>>,
+) -> Link<T, A> {
+    node.map(|node| {
+        let node = unsafe { node.as_ref() };
+        let new_node = Node {
+            key: node.key.clone(),
+            len: node.len,
+            height: node.height,
+            aug: node.aug.clone(),
+            left: clone_tree(node.left, pool),
+            right: clone_tree(node.right, pool),
+        };
+        pool.alloc(new_node)
+    })
+}
+
+/// dstをsrcと同じ形の木にする
+/// 同じ位置にあるノードのメモリは再利用する
+fn clone_tree_from<T: Clone, A: Clone>(
+    dst: &mut Link<T, A>,
+    src: Link<T, A>,
+    pool: &mut impl Pool<Node<T, A>>,
+) {
+    match (dst.map(|mut node| unsafe { node.as_mut() }), src) {
+        (Some(dst_node), Some(src_node)) => {
+            let src_node = unsafe { src_node.as_ref() };
+            dst_node.key.clone_from(&src_node.key);
+            dst_node.len = src_node.len;
+            dst_node.height = src_node.height;
+            dst_node.aug.clone_from(&src_node.aug);
+            clone_tree_from(&mut dst_node.left, src_node.left, pool);
+            clone_tree_from(&mut dst_node.right, src_node.right, pool);
+        }
+        (None, Some(_)) => *dst = clone_tree(src, pool),
+        (Some(_), None) => {
+            traverse_postorder(dst.take(), |node| drop(unsafe { pool.dealloc(node) }))
+        }
+        (None, None) => {}
+    }
+}
+
+/// 昇順に並んだlen個のノードから完全にバランスした木を構築する
+/// NOTE: O(N)
+fn build_sorted<T, A: Augment<T>>(
+    nodes: &mut impl Iterator<Item = NodePtr<T, A>>,
+    len: usize,
+) -> Link<T, A> {
+    if len == 0 {
+        return None;
+    }
+    let left = build_sorted(nodes, len / 2);
+    let mut root = nodes.next().unwrap();
+    let right = build_sorted(nodes, len - len / 2 - 1);
+    let raw_root = unsafe { root.as_mut() };
+    raw_root.left = left;
+    raw_root.right = right;
+    raw_root.fetch();
+    Some(root)
+}
+
 /// rootに新しいノードを挿入する
 /// すでにnew_nodeと同じ値のノードが存在する場合は挿入せずnew_nodeのメモリを解放する
-fn insert_node<T: Ord>(root: &mut Link<T>, new_node: NodePtr<T>) -> bool {
-    fn insert<T: Ord>(root: &mut Link<T>, mut new_node: NodePtr<T>) -> bool {
-        if let Some(node) = root.map(|mut node| unsafe { node.as_mut() }) {
-            match unsafe { new_node.as_ref().key.borrow() }.cmp(&node.key) {
-                Ordering::Equal => {
-                    free(new_node);
+fn insert_node<T: Ord>(
+    root: &mut Link<T>,
+    new_node: NodePtr<T>,
+    pool: &mut impl Pool<Node<T>>,
+) -> bool {
+    insert_node_by(root, new_node, &mut T::cmp, pool)
+}
+
+/// 比較関数cmpによる順序でrootに新しいノードを挿入する
+/// すでにnew_nodeと等しいノードが存在する場合は挿入せずnew_nodeのメモリを解放する
+fn insert_node_by<T>(
+    root: &mut Link<T>,
+    mut new_node: NodePtr<T>,
+    cmp: &mut impl FnMut(&T, &T) -> Ordering,
+    pool: &mut impl Pool<Node<T>>,
+) -> bool {
+    if let Some(node) = root.map(|mut node| unsafe { node.as_mut() }) {
+        match cmp(&unsafe { new_node.as_ref() }.key, &node.key) {
+            Ordering::Equal => {
+                drop(unsafe { pool.dealloc(new_node) });
+                return false;
+            }
+            Ordering::Less => {
+                if !insert_node_by(&mut node.left, new_node, cmp, pool) {
                     return false;
                 }
-                Ordering::Less => {
-                    if !insert(&mut node.left, new_node) {
-                        return false;
-                    }
-                }
-                Ordering::Greater => {
-                    if !insert(&mut node.right, new_node) {
-                        return false;
-                    }
+            }
+            Ordering::Greater => {
+                if !insert_node_by(&mut node.right, new_node, cmp, pool) {
+                    return false;
                 }
             }
-            balance(root);
+        }
+        balance(root);
+    } else {
+        unsafe {
+            new_node.as_mut().left = None;
+            new_node.as_mut().right = None;
+        }
+        *root = Some(new_node);
+    }
+
+    true
+}
+
+/// cmp(x)がEqualとなるノードをrootから切り離して返す
+/// cmp(x)はxが探す要素より小さければGreater，大きければLessを返す
+fn remove_node_by<T, A: Augment<T>>(
+    root: &mut Link<T, A>,
+    cmp: &mut impl FnMut(&T) -> Ordering,
+) -> Link<T, A> {
+    let raw_node = unsafe { (*root)?.as_mut() };
+    let res = match cmp(&raw_node.key) {
+        Ordering::Equal => {
+            let node = *root;
+            *root = merge(raw_node.left.take(), raw_node.right.take());
+            return node;
+        }
+        Ordering::Less => remove_node_by(&mut raw_node.left, cmp),
+        Ordering::Greater => remove_node_by(&mut raw_node.right, cmp),
+    };
+    if res.is_some() {
+        balance(root);
+    }
+    res
+}
+
+/// leftの全要素 < rootの要素 < rightの全要素 であるような2つの木をrootを介して連結し，新たなrootを返す
+/// 計算量は2つの木の高さの差に対してO(|h(left) - h(right)| + 1)
+fn merge_with_root<T, A: Augment<T>>(
+    mut left: Link<T, A>,
+    mut root: NodePtr<T, A>,
+    mut right: Link<T, A>,
+) -> Link<T, A> {
+    let d = node_height(left) - node_height(right);
+
+    if d > 1 {
+        let raw_left = unsafe { left.unwrap().as_mut() };
+        raw_left.right = merge_with_root(raw_left.right, root, right);
+        balance(&mut left);
+        left
+    } else if d < -1 {
+        let raw_right = unsafe { right.unwrap().as_mut() };
+        raw_right.left = merge_with_root(left, root, raw_right.left);
+        balance(&mut right);
+        right
+    } else {
+        unsafe {
+            root.as_mut().left = left;
+            root.as_mut().right = right;
+        }
+        let mut root = Some(root);
+        balance(&mut root);
+        root
+    }
+}
+
+/// keyより小さい要素からなる木，keyと等しいノード，keyより大きい要素からなる木に分割する
+fn split3<T, A, Q>(root: Link<T, A>, key: &Q) -> (Link<T, A>, Link<T, A>, Link<T, A>)
+where
+    T: Borrow<Q>,
+    A: Augment<T>,
+    Q: Ord + ?Sized,
+{
+    let Some(mut node) = root else {
+        return (None, None, None);
+    };
+
+    let (left, right) = {
+        let raw_node = unsafe { node.as_mut() };
+        (raw_node.left.take(), raw_node.right.take())
+    };
+
+    match key.cmp(unsafe { node.as_ref() }.key.borrow()) {
+        Ordering::Equal => {
+            unsafe { node.as_mut() }.fetch();
+            (left, Some(node), right)
+        }
+        Ordering::Less => {
+            let (less, equal, greater) = split3(left, key);
+            (less, equal, merge_with_root(greater, node, right))
+        }
+        Ordering::Greater => {
+            let (less, equal, greater) = split3(right, key);
+            (merge_with_root(left, node, less), equal, greater)
+        }
+    }
+}
+
+/// leftの全要素 < rightの全要素 であるような2つの木を連結して新たなrootを返す
+fn merge<T, A: Augment<T>>(left: Link<T, A>, right: Link<T, A>) -> Link<T, A> {
+    /// nodeの部分木のうち最も右のノードを切り離して新たなrootと切り離したノードを返す
+    fn remove_max<T, A: Augment<T>>(mut node: NodePtr<T, A>) -> (Link<T, A>, NodePtr<T, A>) {
+        let raw_node = unsafe { node.as_mut() };
+        if let Some(right) = raw_node.right {
+            let (tmp, removed) = remove_max(right);
+            raw_node.right = tmp;
+            let mut node = Some(node);
+            balance(&mut node);
+            (node, removed)
         } else {
-            unsafe {
-                new_node.as_mut().left = None;
-                new_node.as_mut().right = None;
-            }
-            *root = Some(new_node);
+            (raw_node.left.take(), node)
+        }
+    }
+
+    match (left, right) {
+        (None, _) => right,
+        (_, None) => left,
+        (Some(left), _) => {
+            let (left, removed) = remove_max(left);
+            merge_with_root(left, removed, right)
+        }
+    }
+}
+
+/// 2つの木の和集合となる木を返す
+/// 同じ要素が両方の木に存在する場合はaのノードを残してbのノードを解放する
+/// 2つの木の要素数をN, M(N >= M)としてO(M log(N/M + 1))
+fn union<T: Ord>(a: Link<T>, b: Link<T>, pool: &mut impl Pool<Node<T>>) -> Link<T> {
+    let (Some(mut node_a), Some(mut node_b)) = (a, b) else {
+        return a.or(b);
+    };
+
+    if node_len(a) >= node_len(b) {
+        let (left_a, right_a) = {
+            let raw_a = unsafe { node_a.as_mut() };
+            (raw_a.left.take(), raw_a.right.take())
+        };
+        let (left_b, equal, right_b) = split3(b, &unsafe { node_a.as_ref() }.key);
+        if let Some(equal) = equal {
+            drop(unsafe { pool.dealloc(equal) });
+        }
+        let left = union(left_a, left_b, pool);
+        let right = union(right_a, right_b, pool);
+        merge_with_root(left, node_a, right)
+    } else {
+        let (left_b, right_b) = {
+            let raw_b = unsafe { node_b.as_mut() };
+            (raw_b.left.take(), raw_b.right.take())
+        };
+        let (left_a, equal, right_a) = split3(a, &unsafe { node_b.as_ref() }.key);
+        let root = if let Some(equal) = equal {
+            drop(unsafe { pool.dealloc(node_b) });
+            equal
+        } else {
+            node_b
+        };
+        let left = union(left_a, left_b, pool);
+        let right = union(right_a, right_b, pool);
+        merge_with_root(left, root, right)
+    }
+}
+
+/// 2つの木の和集合となる木を返す
+/// 一方の木の全要素が他方の木の全要素より大きい場合はO(log N)で連結する
+fn union_or_merge<T: Ord>(a: Link<T>, b: Link<T>, pool: &mut impl Pool<Node<T>>) -> Link<T> {
+    fn first<'a, T>(mut node: NodePtr<T>) -> &'a T {
+        while let Some(left) = unsafe { node.as_ref() }.left {
+            node = left;
+        }
+        &unsafe { node.as_ref() }.key
+    }
+
+    fn last<'a, T>(mut node: NodePtr<T>) -> &'a T {
+        while let Some(right) = unsafe { node.as_ref() }.right {
+            node = right;
+        }
+        &unsafe { node.as_ref() }.key
+    }
+
+    let (Some(node_a), Some(node_b)) = (a, b) else {
+        return a.or(b);
+    };
+
+    if last(node_a) < first(node_b) {
+        merge(a, b)
+    } else if last(node_b) < first(node_a) {
+        merge(b, a)
+    } else {
+        union(a, b, pool)
+    }
+}
+
+/// predを満たす要素からなる木と満たさない要素からなる木に分割する
+/// predは昇順に並べた要素に対してtrue, ..., true, false, ..., falseとなるものに限る
+/// 木の高さに対してO(log N)
+fn split_by<T, A: Augment<T>>(
+    root: Link<T, A>,
+    pred: &mut impl FnMut(&T) -> bool,
+) -> (Link<T, A>, Link<T, A>) {
+    let Some(mut node) = root else {
+        return (None, None);
+    };
+
+    let (left, right) = {
+        let raw_node = unsafe { node.as_mut() };
+        (raw_node.left.take(), raw_node.right.take())
+    };
+
+    if pred(&unsafe { node.as_ref() }.key) {
+        let (l, r) = split_by(right, pred);
+        (merge_with_root(left, node, l), r)
+    } else {
+        let (l, r) = split_by(left, pred);
+        (l, merge_with_root(r, node, right))
+    }
+}
+
+/// keyより小さい要素からなる木とkey以上の要素からなる木に分割する
+/// 木の高さに対してO(log N)
+fn split<T, Q>(root: Link<T>, key: &Q) -> (Link<T>, Link<T>)
+where
+    T: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    let (less, equal, greater) = split3(root, key);
+    match equal {
+        Some(equal) => (less, merge_with_root(None, equal, greater)),
+        None => (less, greater),
+    }
+}
+
+/// predを満たす要素の数
+/// predの条件はsplit_byと同じ
+fn count_by<T, A>(root: Link<T, A>, mut pred: impl FnMut(&T) -> bool) -> usize {
+    let mut res = 0;
+    let mut cur = root;
+    while let Some(node) = cur.map(|node| unsafe { node.as_ref() }) {
+        if pred(&node.key) {
+            res += node_len(node.left) + 1;
+            cur = node.right;
+        } else {
+            cur = node.left;
         }
+    }
+    res
+}
+
+/// 昇順index番目のノードを切り離して返す
+/// (index < Nの場合のみ呼び出す)
+fn remove_nth<T, A: Augment<T>>(root: &mut Link<T, A>, index: usize) -> NodePtr<T, A> {
+    let mut node = root.unwrap();
+    let raw_node = unsafe { node.as_mut() };
+    let left_len = node_len(raw_node.left);
+    let res = if index < left_len {
+        remove_nth(&mut raw_node.left, index)
+    } else if index > left_len {
+        remove_nth(&mut raw_node.right, index - left_len - 1)
+    } else {
+        *root = merge(raw_node.left.take(), raw_node.right.take());
+        return node;
+    };
+    balance(root);
+    res
+}
 
-        true
+/// pred(x)がtrueとなる最後のノード
+/// predの条件はsplit_byと同じ
+fn last_by<T, A>(root: Link<T, A>, mut pred: impl FnMut(&T) -> bool) -> Link<T, A> {
+    let mut res = None;
+    let mut cur = root;
+    while let Some(node) = cur {
+        let raw_node = unsafe { node.as_ref() };
+        if pred(&raw_node.key) {
+            res = Some(node);
+            cur = raw_node.right;
+        } else {
+            cur = raw_node.left;
+        }
     }
+    res
+}
 
-    insert(root, new_node)
+/// pred(x)がfalseとなる最初のノード
+/// predの条件はsplit_byと同じ
+fn first_not_by<T, A>(root: Link<T, A>, mut pred: impl FnMut(&T) -> bool) -> Link<T, A> {
+    let mut res = None;
+    let mut cur = root;
+    while let Some(node) = cur {
+        let raw_node = unsafe { node.as_ref() };
+        if pred(&raw_node.key) {
+            cur = raw_node.right;
+        } else {
+            res = Some(node);
+            cur = raw_node.left;
+        }
+    }
+    res
 }
 
-/// AVL木によるordered setの実装
-#[derive(Clone)]
-pub struct AvlTreeSet<T> {
+/// ノードごとにBoxで確保するAvlTreeSetIn
+pub type AvlTreeSet<T> = AvlTreeSetIn<T, Boxed>;
+
+/// ノードをまとめて確保した領域に格納するAvlTreeSetIn
+/// 公開APIはAvlTreeSetと同じ
+pub type ArenaAvlTreeSet<T> = AvlTreeSetIn<T, Arena>;
+
+/// ノードの領域をSで指定するAVL木によるordered set
+///
+/// 要素を所有するのでTに応じてSend・Syncとなり，イテレータは木より長く生存できない
+///
+/// ```compile_fail
+/// use avl_tree_set::AvlTreeSet;
+///
+/// let iter = {
+///     let set = AvlTreeSet::from([1, 2, 3]);
+///     set.iter()
+/// };
+/// ```
+///
+/// ```compile_fail
+/// use avl_tree_set::AvlTreeSet;
+/// use std::rc::Rc;
+///
+/// let set = AvlTreeSet::from([Rc::new(1)]);
+/// std::thread::spawn(move || set.len());
+/// ```
+pub struct AvlTreeSetIn<T, S: Storage> {
     root: Link<T>,
+    pool: NodePool<T, S>,
+    phantom: PhantomData<Box<Node<T>>>,
 }
 
-impl<T> AvlTreeSet<T> {
+type NodePool<T, S> = <S as Storage>::Pool<Node<T>>;
+
+// NOTE: ノードはBoxと同様に木が排他的に所有する
+unsafe impl<T: Send, S: Storage> Send for AvlTreeSetIn<T, S> {}
+unsafe impl<T: Sync, S: Storage> Sync for AvlTreeSetIn<T, S> {}
+
+impl<T, S: Storage> AvlTreeSetIn<T, S> {
     pub fn new() -> Self {
         Self::default()
     }
@@ -230,6 +671,44 @@ impl<T> AvlTreeSet<T> {
         *self = Self::new();
     }
 
+    /// 昇順に並んだ列から構築する
+    /// 等しい要素が連続する場合は最初の要素だけを残す
+    /// NOTE: O(N)
+    ///
+    /// # Panics
+    /// iterが昇順に並んでいない場合
+    pub fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> Self
+    where
+        T: Ord,
+    {
+        Self::from_sorted_vec(iter.into_iter().collect())
+    }
+
+    /// 昇順に並んだVecから構築する
+    /// 等しい要素が連続する場合は最初の要素だけを残す
+    /// NOTE: O(N)
+    ///
+    /// # Panics
+    /// vが昇順に並んでいない場合
+    pub fn from_sorted_vec(mut v: Vec<T>) -> Self
+    where
+        T: Ord,
+    {
+        assert!(v.is_sorted(), "input is not sorted");
+        v.dedup();
+        let len = v.len();
+        let mut pool = NodePool::<T, S>::default();
+        let root = build_sorted(
+            &mut v.into_iter().map(|key| Node::new_in(key, &mut pool)),
+            len,
+        );
+        Self {
+            root,
+            pool,
+            phantom: PhantomData,
+        }
+    }
+
     pub fn len(&self) -> usize {
         node_len(self.root)
     }
@@ -242,31 +721,58 @@ impl<T> AvlTreeSet<T> {
     where
         T: Ord,
     {
-        insert_node(&mut self.root, Node::new(key))
+        insert_node(
+            &mut self.root,
+            Node::new_in(key, &mut self.pool),
+            &mut self.pool,
+        )
     }
 
-    pub fn contains(&self, key: &T) -> bool
+    pub fn contains<Q>(&self, key: &Q) -> bool
     where
-        T: Ord,
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// keyと等しい要素
+    pub fn get<Q>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
     {
-        fn contains<T: Ord>(node: Link<T>, key: &T) -> bool {
-            if let Some(node) = node.map(|node| unsafe { node.as_ref() }) {
-                match key.cmp(&node.key) {
-                    Ordering::Equal => true,
-                    Ordering::Less => contains(node.left, key),
-                    Ordering::Greater => contains(node.right, key),
+        fn get<T, Q>(node: Link<T>, key: &Q) -> Link<T>
+        where
+            T: Borrow<Q>,
+            Q: Ord + ?Sized,
+        {
+            if let Some(raw_node) = node.map(|node| unsafe { node.as_ref() }) {
+                match key.cmp(raw_node.key.borrow()) {
+                    Ordering::Equal => node,
+                    Ordering::Less => get(raw_node.left, key),
+                    Ordering::Greater => get(raw_node.right, key),
                 }
             } else {
-                false
+                None
             }
         }
-        contains(self.root, key)
+        get(self.root, key).map(|node| &unsafe { node.as_ref() }.key)
     }
 
     pub fn remove<Q>(&mut self, key: &Q) -> bool
     where
         T: Borrow<Q>,
-        Q: Ord,
+        Q: Ord + ?Sized,
+    {
+        self.take(key).is_some()
+    }
+
+    /// keyと等しい要素を削除して返す
+    pub fn take<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
     {
         fn remove_min<T>(node: &mut Link<T>) -> Link<T> {
             unsafe {
@@ -288,7 +794,7 @@ impl<T> AvlTreeSet<T> {
         fn remove<T, Q>(node: &mut Link<T>, key: &Q) -> Link<T>
         where
             T: Borrow<Q>,
-            Q: Ord,
+            Q: Ord + ?Sized,
         {
             let res = if let Some(raw_node) = node.map(|mut node| unsafe { node.as_mut() }) {
                 match key.cmp(raw_node.key.borrow()) {
@@ -334,14 +840,102 @@ impl<T> AvlTreeSet<T> {
             res
         }
 
-        remove(&mut self.root, key).map(|node| free(node)).is_some()
+        remove(&mut self.root, key).map(|node| unsafe { self.pool.dealloc(node) }.key)
     }
 
-    /// 昇順n番目の要素
-    pub fn get_nth(&self, mut n: usize) -> Option<&T>
+    /// keyと等しい要素があればkeyで置き換えて元の要素を返す
+    /// 存在しない場合はkeyを挿入してNoneを返す
+    pub fn replace(&mut self, key: T) -> Option<T>
     where
         T: Ord,
     {
+        let mut cur = self.root;
+        while let Some(node) = cur.map(|mut node| unsafe { node.as_mut() }) {
+            match key.cmp(&node.key) {
+                Ordering::Equal => return Some(std::mem::replace(&mut node.key, key)),
+                Ordering::Less => cur = node.left,
+                Ordering::Greater => cur = node.right,
+            }
+        }
+        self.insert(key);
+        None
+    }
+
+    /// fがtrueを返す要素だけを残す
+    /// NOTE: 残ったノードから木を組み直すのでO(N)
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        /// fがpanicした場合も，まだ調べていないノードを残したまま木を組み直す
+        struct Guard<'a, T, P: Pool<Node<T>>> {
+            root: &'a mut Link<T>,
+            pool: &'a mut P,
+            /// 昇順に並べたノード
+            nodes: Vec<NodePtr<T>>,
+            /// 残すノードの数(nodes[..kept]が残すノード)
+            kept: usize,
+            /// 次に調べるノードの位置
+            index: usize,
+        }
+
+        impl<T, P: Pool<Node<T>>> Drop for Guard<'_, T, P> {
+            fn drop(&mut self) {
+                self.nodes.copy_within(self.index.., self.kept);
+                let len = self.kept + (self.nodes.len() - self.index);
+                *self.root = build_sorted(&mut self.nodes.drain(..len), len);
+            }
+        }
+
+        let mut nodes = Vec::with_capacity(self.len());
+        traverse_inorder(self.root.take(), |node| nodes.push(node));
+        let mut guard = Guard {
+            root: &mut self.root,
+            pool: &mut self.pool,
+            nodes,
+            kept: 0,
+            index: 0,
+        };
+        while guard.index < guard.nodes.len() {
+            let node = guard.nodes[guard.index];
+            if f(&unsafe { node.as_ref() }.key) {
+                guard.nodes[guard.kept] = node;
+                guard.kept += 1;
+                guard.index += 1;
+            } else {
+                guard.index += 1;
+                drop(unsafe { guard.pool.dealloc(node) });
+            }
+        }
+    }
+
+    /// predがtrueを返す要素を昇順に削除しながら返すイテレータ
+    /// 途中で破棄した場合，残りの要素は削除されない
+    /// NOTE: 削除する要素1つあたりO(log N)，残す要素1つあたりならしO(1)
+    pub fn extract_if<F: FnMut(&T) -> bool>(&mut self, pred: F) -> ExtractIf<'_, T, F, S> {
+        ExtractIf {
+            cursor: CursorMut::new(&mut self.root, &mut self.pool, 0),
+            pred,
+        }
+    }
+
+    /// 範囲内の要素を木から切り離し，昇順に返すイテレータ
+    /// NOTE: 切り離す要素数Kに対してO(log N + K)
+    pub fn drain_range<Q, R>(&mut self, range: R) -> IntoIter<T, S>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let (left, rest) = split_by(self.root.take(), &mut |x: &T| {
+            !is_after_start(x.borrow(), range.start_bound())
+        });
+        let (mid, right) = split_by(rest, &mut |x: &T| {
+            is_before_end(x.borrow(), range.end_bound())
+        });
+        self.root = merge(left, right);
+        IntoIter::new(mid, self.pool.share())
+    }
+
+    /// 昇順n番目の要素
+    pub fn get_nth(&self, mut n: usize) -> Option<&T> {
         let mut cur = &self.root;
         while let Some(node) = cur.map(|node| unsafe { node.as_ref() }) {
             let left_len = node_len(node.left);
@@ -358,10 +952,7 @@ impl<T> AvlTreeSet<T> {
     }
 
     /// 降順n番目の要素
-    pub fn get_nth_back(&self, mut n: usize) -> Option<&T>
-    where
-        T: Ord,
-    {
+    pub fn get_nth_back(&self, mut n: usize) -> Option<&T> {
         let mut cur = &self.root;
         while let Some(node) = cur.map(|node| unsafe { node.as_ref() }) {
             let right_len = node_len(node.right);
@@ -377,292 +968,1024 @@ impl<T> AvlTreeSet<T> {
         None
     }
 
-    // key以上最小の要素
-    pub fn lower_bound(&self, key: &T) -> Option<&T>
+    /// keyより小さい要素の数
+    /// NOTE: O(log N)
+    pub fn rank<Q>(&self, key: &Q) -> usize
     where
-        T: Ord,
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
     {
-        let mut cur = &self.root;
-        let mut res = None;
-
-        while let Some(node) = cur.map(|node| unsafe { node.as_ref() }) {
-            match key.cmp(&node.key) {
-                Ordering::Equal | Ordering::Less => {
-                    res = cur.map(|node| &unsafe { node.as_ref() }.key);
-                    cur = &node.left;
-                }
-                _ => cur = &node.right,
-            }
-        }
-
-        res
+        count_by(self.root, |x| x.borrow() < key)
     }
 
-    pub fn range<'a, B>(&'a self, range: B) -> RangeIter<'a, T, B>
+    /// 範囲内の要素の数
+    /// NOTE: O(log N)
+    pub fn count_range<Q, R>(&self, range: R) -> usize
     where
-        T: Ord,
-        B: RangeBounds<T>,
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
     {
-        RangeIter::new(&self.root, range)
+        let start = count_by(self.root, |x| {
+            !is_after_start(x.borrow(), range.start_bound())
+        });
+        let end = count_by(self.root, |x| is_before_end(x.borrow(), range.end_bound()));
+        end.saturating_sub(start)
     }
 
-    /// NOTE: 2つのAVL木の要素数をN, Mに対してO(min(N+M)log N)
-    pub fn append(&mut self, other: &mut Self)
+    /// 範囲内で昇順n番目の要素
+    /// NOTE: O(log N)
+    pub fn nth_in_range<Q, R>(&self, range: R, n: usize) -> Option<&T>
     where
-        T: Ord,
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
     {
-        if self.len() < other.len() {
-            swap(self, other);
-        }
-
-        traverse_postorder(other.root.take(), |node| {
-            insert_node(&mut self.root, node);
+        let start = count_by(self.root, |x| {
+            !is_after_start(x.borrow(), range.start_bound())
         });
+        let end = count_by(self.root, |x| is_before_end(x.borrow(), range.end_bound()));
+        if start.saturating_add(n) < end {
+            self.get_nth(start + n)
+        } else {
+            None
+        }
     }
 
-    /// NOTE: AVL木の要素数Nに対してO(N log N)
-    /// ↑本当？　もっと効率的な方法があるのでは
-    /// AVL木の性質を利用したいが単純な分割では木のバランスが崩れる
-    pub fn split_off(&mut self, key: &T) -> Self
+    /// key以上最小の要素(ceiling)
+    pub fn lower_bound<Q>(&self, key: &Q) -> Option<&T>
     where
-        T: Ord,
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
     {
-        let mut left = None;
-        let mut right = None;
-
-        traverse_postorder(self.root.take(), |node| {
-            match unsafe { node.as_ref() }.key.borrow().cmp(&key) {
-                Ordering::Less => {
-                    insert_node(&mut left, node);
-                }
-                _ => {
-                    insert_node(&mut right, node);
-                }
-            }
-        });
-
-        *self = Self { root: left };
-        Self { root: right }
+        let node = first_not_by(self.root, |x| x.borrow() < key)?;
+        Some(&unsafe { node.as_ref() }.key)
     }
 
-    pub fn iter(&self) -> RangeIter<'_, T, RangeFull>
+    /// keyより大きい最小の要素(successor)
+    pub fn upper_bound<Q>(&self, key: &Q) -> Option<&T>
     where
-        T: Ord,
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
     {
-        RangeIter::new(&self.root, ..)
+        let node = first_not_by(self.root, |x| x.borrow() <= key)?;
+        Some(&unsafe { node.as_ref() }.key)
     }
-}
 
-impl<T> Drop for AvlTreeSet<T> {
-    fn drop(&mut self) {
-        traverse_postorder(self.root, |node| free(node));
+    /// key以下最大の要素
+    pub fn floor<Q>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let node = last_by(self.root, |x| x.borrow() <= key)?;
+        Some(&unsafe { node.as_ref() }.key)
     }
-}
 
-impl<T> Default for AvlTreeSet<T> {
-    fn default() -> Self {
-        Self { root: None }
+    /// keyより小さい最大の要素
+    pub fn predecessor<Q>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let node = last_by(self.root, |x| x.borrow() < key)?;
+        Some(&unsafe { node.as_ref() }.key)
     }
-}
 
-impl<T: Ord + PartialEq> PartialEq for AvlTreeSet<T> {
-    fn eq(&self, other: &Self) -> bool {
-        self.iter().eq(other.iter())
+    /// 最小の要素
+    pub fn first(&self) -> Option<&T> {
+        self.get_nth(0)
     }
-}
-
-impl<T: Ord + Eq> Eq for AvlTreeSet<T> {}
 
-impl<T: Ord + PartialOrd> PartialOrd for AvlTreeSet<T> {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.iter().partial_cmp(other.iter())
+    /// 最大の要素
+    pub fn last(&self) -> Option<&T> {
+        self.get_nth_back(0)
     }
-}
 
-impl<T: Ord> Ord for AvlTreeSet<T> {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.iter().cmp(other.iter())
+    /// 最小の要素を削除して返す
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.pop_nth(0)
     }
-}
 
-impl<T: Ord + Hash> Hash for AvlTreeSet<T> {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.iter().for_each(|item| item.hash(state));
+    /// 最大の要素を削除して返す
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.pop_nth(self.len().checked_sub(1)?)
     }
-}
-
-impl<'a, T: Ord> IntoIterator for &'a AvlTreeSet<T> {
-    type IntoIter = RangeIter<'a, T, RangeFull>;
-    type Item = &'a T;
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter()
+    fn pop_nth(&mut self, n: usize) -> Option<T> {
+        (n < self.len()).then(|| {
+            let node = remove_nth(&mut self.root, n);
+            unsafe { self.pool.dealloc(node) }.key
+        })
     }
-}
 
-impl<T> IntoIterator for AvlTreeSet<T> {
-    type IntoIter = IntoIter<T>;
-    type Item = T;
+    /// key以上最小の要素を指すカーソル(存在しない場合はghostを指す)
+    pub fn lower_bound_cursor<Q>(&self, key: &Q) -> Cursor<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Cursor::new(self.root, self.rank(key))
+    }
 
-    fn into_iter(mut self) -> Self::IntoIter {
-        IntoIter::new(self.root.take())
+    /// keyより大きい最小の要素を指すカーソル(存在しない場合はghostを指す)
+    pub fn upper_bound_cursor<Q>(&self, key: &Q) -> Cursor<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Cursor::new(self.root, count_by(self.root, |x| x.borrow() <= key))
     }
-}
 
-impl<T: Ord> Extend<T> for AvlTreeSet<T> {
-    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+    /// key以上最小の要素を指す変更可能なカーソル(存在しない場合はghostを指す)
+    pub fn lower_bound_cursor_mut<Q>(&mut self, key: &Q) -> CursorMut<'_, T, S>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let index = self.rank(key);
+        CursorMut::new(&mut self.root, &mut self.pool, index)
+    }
+
+    /// keyより大きい最小の要素を指す変更可能なカーソル(存在しない場合はghostを指す)
+    pub fn upper_bound_cursor_mut<Q>(&mut self, key: &Q) -> CursorMut<'_, T, S>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let index = count_by(self.root, |x| x.borrow() <= key);
+        CursorMut::new(&mut self.root, &mut self.pool, index)
+    }
+
+    /// 範囲内の要素を昇順に返すイテレータ
+    /// NOTE: 構築はO(log N)，1要素あたりならしO(1)
+    pub fn range<Q, R>(&self, range: R) -> RangeIter<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        RangeIter::new(self.root, range)
+    }
+
+    /// otherの全要素をselfに移動する
+    /// 同じ要素が両方に存在する場合はselfの要素が残る
+    /// NOTE: 2つのAVL木の要素数をN, M(N >= M)に対してO(M log(N/M + 1))
+    /// 一方の全要素が他方の全要素より大きい場合はO(log N)
+    pub fn append(&mut self, other: &mut Self)
+    where
+        T: Ord,
+    {
+        self.pool.absorb(&mut other.pool);
+        self.root = union_or_merge(self.root.take(), other.root.take(), &mut self.pool);
+    }
+
+    /// 2つの集合の和集合を返す
+    /// 同じ要素が両方に存在する場合はselfの要素が残る
+    /// NOTE: appendと同じ計算量
+    pub fn into_union(mut self, mut other: Self) -> Self
+    where
+        T: Ord,
+    {
+        self.append(&mut other);
+        self
+    }
+
+    /// key以上の要素を切り離して返す
+    /// NOTE: AVL木の要素数Nに対してO(log N)
+    /// 根からkeyまでの経路上のノードを高さを考慮して連結し直す
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (left, right) = split(self.root.take(), key);
+        self.root = left;
+        Self {
+            root: right,
+            pool: self.pool.share(),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn iter(&self) -> RangeIter<'_, T> {
+        RangeIter(NodeRange::new(self.root, |_| true, |_| true))
+    }
+
+    /// selfの全要素がotherに含まれるか
+    pub fn is_subset(&self, other: &Self) -> bool
+    where
+        T: Ord,
+    {
+        if self.len() > other.len() {
+            return false;
+        }
+
+        let (Some(first), Some(last)) = (self.get_nth(0), self.get_nth_back(0)) else {
+            return true;
+        };
+
+        // selfの最小値・最大値がotherの範囲外であれば部分集合ではない
+        if other.get_nth(0).is_none_or(|x| first < x)
+            || other.get_nth_back(0).is_none_or(|x| last > x)
+        {
+            return false;
+        }
+
+        if is_lopsided(self.len(), other.len()) {
+            self.iter().all(|x| other.contains(x))
+        } else {
+            let mut other_iter = other.range(first..=last);
+            self.iter().all(|x| other_iter.find(|&y| y >= x) == Some(x))
+        }
+    }
+
+    /// otherの全要素がselfに含まれるか
+    pub fn is_superset(&self, other: &Self) -> bool
+    where
+        T: Ord,
+    {
+        other.is_subset(self)
+    }
+
+    /// selfとotherに共通する要素が存在しないか
+    /// 要素数が偏っている場合は小さい方の各要素で他方を探索する
+    pub fn is_disjoint(&self, other: &Self) -> bool
+    where
+        T: Ord,
+    {
+        self.intersection(other).next().is_none()
+    }
+
+    /// selfとotherの和集合を昇順に返すイテレータ
+    pub fn union<'a>(&'a self, other: &'a Self) -> Union<'a, T>
+    where
+        T: Ord,
+    {
+        Union {
+            a: SetIter::new(self.iter()),
+            b: SetIter::new(other.iter()),
+        }
+    }
+
+    /// selfとotherの積集合を昇順に返すイテレータ
+    /// 一方が他方に比べて十分小さい場合は小さい方を走査して他方を探索する
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> Intersection<'a, T, S>
+    where
+        T: Ord,
+    {
+        let (small, large) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+
+        let inner = if is_lopsided(small.len(), large.len()) {
+            IntersectionInner::Search {
+                small: small.iter(),
+                large,
+            }
+        } else {
+            IntersectionInner::Stitch {
+                a: SetIter::new(self.iter()),
+                b: SetIter::new(other.iter()),
+            }
+        };
+
+        Intersection { inner }
+    }
+
+    /// selfとotherの差集合(selfに含まれotherに含まれない要素)を昇順に返すイテレータ
+    /// selfがotherに比べて十分小さい場合はselfを走査してotherを探索する
+    pub fn difference<'a>(&'a self, other: &'a Self) -> Difference<'a, T, S>
+    where
+        T: Ord,
+    {
+        let inner = if is_lopsided(self.len(), other.len()) {
+            DifferenceInner::Search {
+                a: self.iter(),
+                b: other,
+            }
+        } else {
+            DifferenceInner::Stitch {
+                a: SetIter::new(self.iter()),
+                b: SetIter::new(other.iter()),
+            }
+        };
+
+        Difference { inner }
+    }
+
+    /// selfとotherの対称差を昇順に返すイテレータ
+    pub fn symmetric_difference<'a>(&'a self, other: &'a Self) -> SymmetricDifference<'a, T>
+    where
+        T: Ord,
+    {
+        SymmetricDifference {
+            a: SetIter::new(self.iter()),
+            b: SetIter::new(other.iter()),
+        }
+    }
+}
+
+impl<T, S: Storage> Drop for AvlTreeSetIn<T, S> {
+    fn drop(&mut self) {
+        free_tree(self.root, &mut self.pool);
+    }
+}
+
+impl<T: Clone, S: Storage> Clone for AvlTreeSetIn<T, S> {
+    fn clone(&self) -> Self {
+        let mut pool = NodePool::<T, S>::default();
+        Self {
+            root: clone_tree(self.root, &mut pool),
+            pool,
+            phantom: PhantomData,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        clone_tree_from(&mut self.root, source.root, &mut self.pool);
+    }
+}
+
+impl<T, S: Storage> Default for AvlTreeSetIn<T, S> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            pool: NodePool::<T, S>::default(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Ord + PartialEq, S: Storage> PartialEq for AvlTreeSetIn<T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Ord + Eq, S: Storage> Eq for AvlTreeSetIn<T, S> {}
+
+#[allow(clippy::non_canonical_partial_ord_impl)]
+impl<T: Ord + PartialOrd, S: Storage> PartialOrd for AvlTreeSetIn<T, S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T: Ord, S: Storage> Ord for AvlTreeSetIn<T, S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<T: Ord + Hash, S: Storage> Hash for AvlTreeSetIn<T, S> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.iter().for_each(|item| item.hash(state));
+    }
+}
+
+impl<'a, T, S: Storage> IntoIterator for &'a AvlTreeSetIn<T, S> {
+    type IntoIter = RangeIter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, S: Storage> IntoIterator for AvlTreeSetIn<T, S> {
+    type IntoIter = IntoIter<T, S>;
+    type Item = T;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        IntoIter::new(self.root.take(), take(&mut self.pool))
+    }
+}
+
+impl<T: Ord, S: Storage> Extend<T> for AvlTreeSetIn<T, S> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
         iter.into_iter().for_each(|x| {
             self.insert(x);
         });
     }
 }
 
-impl<'a, T: 'a + Ord + Copy> Extend<&'a T> for AvlTreeSet<T> {
+impl<'a, T: 'a + Ord + Copy, S: Storage> Extend<&'a T> for AvlTreeSetIn<T, S> {
     fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
         self.extend(iter.into_iter().cloned());
     }
 }
 
-impl<T: Ord> FromIterator<T> for AvlTreeSet<T> {
+impl<T: Ord, S: Storage> FromIterator<T> for AvlTreeSetIn<T, S> {
+    /// 昇順に並んでいない場合は安定ソートしてから構築する
+    /// 等しい要素は最初に現れたものを残す
+    /// NOTE: 昇順に並んでいればO(N)，そうでなければO(N log N)
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
-        let mut res = Self::new();
-        res.extend(iter);
-        res
+        let mut v = iter.into_iter().collect::<Vec<_>>();
+        if !v.is_sorted() {
+            v.sort();
+        }
+        Self::from_sorted_vec(v)
     }
 }
 
-impl<T: Ord> From<Vec<T>> for AvlTreeSet<T> {
+impl<T: Ord, S: Storage> From<Vec<T>> for AvlTreeSetIn<T, S> {
     fn from(v: Vec<T>) -> Self {
         Self::from_iter(v)
     }
 }
 
-impl<T: Ord, const N: usize> From<[T; N]> for AvlTreeSet<T> {
+impl<T: Ord, S: Storage, const N: usize> From<[T; N]> for AvlTreeSetIn<T, S> {
     fn from(v: [T; N]) -> Self {
         Self::from_iter(v)
     }
 }
 
-impl<T: Ord + Debug> Debug for AvlTreeSet<T> {
+impl<T: Ord + Debug, S: Storage> Debug for AvlTreeSetIn<T, S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_set().entries(self.iter()).finish()
     }
 }
 
 // TODO: ???
-pub struct IntoIter<T> {
+pub struct IntoIter<T, S: Storage = Boxed> {
     iter: std::vec::IntoIter<NodePtr<T>>,
+    pool: NodePool<T, S>,
+    phantom: PhantomData<Box<Node<T>>>,
 }
 
-impl<T> IntoIter<T> {
-    fn new(root: Link<T>) -> Self {
+unsafe impl<T: Send, S: Storage> Send for IntoIter<T, S> {}
+unsafe impl<T: Sync, S: Storage> Sync for IntoIter<T, S> {}
+
+impl<T, S: Storage> IntoIter<T, S> {
+    fn new(root: Link<T>, pool: NodePool<T, S>) -> Self {
         let mut stack = Vec::with_capacity(node_len(root));
         traverse_inorder(root, |node| {
             stack.push(node);
         });
         IntoIter {
             iter: stack.into_iter(),
+            pool,
+            phantom: PhantomData,
         }
     }
 }
 
-impl<T> Iterator for IntoIter<T> {
+impl<T, S: Storage> Iterator for IntoIter<T, S> {
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
         let node = self.iter.next()?;
-        let boxed = unsafe { Box::from_raw(node.as_ptr()) };
-        Some(boxed.key)
+        Some(unsafe { self.pool.dealloc(node) }.key)
     }
 }
 
-impl<T> DoubleEndedIterator for IntoIter<T> {
+impl<T, S: Storage> DoubleEndedIterator for IntoIter<T, S> {
     fn next_back(&mut self) -> Option<Self::Item> {
         let node = self.iter.next_back()?;
-        let boxed = unsafe { Box::from_raw(node.as_ptr()) };
-        Some(boxed.key)
+        Some(unsafe { self.pool.dealloc(node) }.key)
     }
 }
 
-impl<T> Drop for IntoIter<T> {
+impl<T, S: Storage> Drop for IntoIter<T, S> {
     fn drop(&mut self) {
-        for node in take(&mut self.iter) {
-            free(node)
+        if <NodePool<T, S> as Pool<_>>::FREE_EACH || needs_drop::<T>() {
+            for node in take(&mut self.iter) {
+                drop(unsafe { self.pool.dealloc(node) });
+            }
         }
     }
 }
 
-// TODO: 全部走査しているので効率が悪い
-pub struct RangeIter<'a, T, B> {
-    stack_left: Vec<&'a NodePtr<T>>,
-    stack_right: Vec<&'a NodePtr<T>>,
-    range: B,
+/// extract_ifが返すイテレータ
+pub struct ExtractIf<'a, T, F, S: Storage = Boxed> {
+    /// 次に調べる要素を指すカーソル
+    cursor: CursorMut<'a, T, S>,
+    pred: F,
 }
 
-impl<'a, T: Ord, B: RangeBounds<T>> RangeIter<'a, T, B> {
-    fn new(root: &'a Link<T>, range: B) -> Self {
-        let mut res = Self {
-            stack_left: vec![],
-            stack_right: vec![],
-            range,
-        };
-        res.push_left(root);
-        res.push_right(root);
-        res
+impl<'a, T, F: FnMut(&T) -> bool, S: Storage> Iterator for ExtractIf<'a, T, F, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(key) = self.cursor.current() {
+            if (self.pred)(key) {
+                return self.cursor.remove_current();
+            }
+            self.cursor.move_next();
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.cursor.remaining()))
+    }
+}
+
+/// keyがstartで指定される下限を満たすか
+#[inline]
+fn is_after_start<Q: Ord + ?Sized>(key: &Q, start: Bound<&Q>) -> bool {
+    is_after_start_by(key, start, Q::cmp)
+}
+
+/// keyがendで指定される上限を満たすか
+#[inline]
+fn is_before_end<Q: Ord + ?Sized>(key: &Q, end: Bound<&Q>) -> bool {
+    is_before_end_by(key, end, Q::cmp)
+}
+
+/// 比較関数cmpによる順序でkeyがstartで指定される下限を満たすか
+#[inline]
+fn is_after_start_by<Q: ?Sized>(
+    key: &Q,
+    start: Bound<&Q>,
+    cmp: impl FnOnce(&Q, &Q) -> Ordering,
+) -> bool {
+    match start {
+        Bound::Included(start) => cmp(key, start) != Ordering::Less,
+        Bound::Excluded(start) => cmp(key, start) == Ordering::Greater,
+        Bound::Unbounded => true,
+    }
+}
+
+/// 比較関数cmpによる順序でkeyがendで指定される上限を満たすか
+#[inline]
+fn is_before_end_by<Q: ?Sized>(
+    key: &Q,
+    end: Bound<&Q>,
+    cmp: impl FnOnce(&Q, &Q) -> Ordering,
+) -> bool {
+    match end {
+        Bound::Included(end) => cmp(key, end) != Ordering::Greater,
+        Bound::Excluded(end) => cmp(key, end) == Ordering::Less,
+        Bound::Unbounded => true,
+    }
+}
+
+/// 範囲内のノードを前後から走査する
+/// 構築時に範囲の両端まで降りるのでO(log N)，以降は1要素あたりならしO(1)
+struct NodeRange<'a, T, A = ()> {
+    stack_left: Vec<NodePtr<T, A>>,
+    stack_right: Vec<NodePtr<T, A>>,
+    /// まだ返していない範囲内のノード数
+    len: usize,
+    phantom: PhantomData<&'a Node<T, A>>,
+}
+
+// NOTE: &Node<T, A>と同様
+unsafe impl<T: Sync, A: Sync> Send for NodeRange<'_, T, A> {}
+unsafe impl<T: Sync, A: Sync> Sync for NodeRange<'_, T, A> {}
+
+impl<'a, T, A> NodeRange<'a, T, A> {
+    /// 下限を満たす要素の列と上限を満たす要素の列がそれぞれ接尾辞・接頭辞となるように
+    /// after_startとbefore_endを与える
+    fn new(
+        root: Link<T, A>,
+        mut after_start: impl FnMut(&T) -> bool,
+        mut before_end: impl FnMut(&T) -> bool,
+    ) -> Self {
+        let mut stack_left = vec![];
+        let mut stack_right = vec![];
+        // 下限を満たさない要素数
+        let mut start_rank = 0;
+        // 上限を満たす要素数
+        let mut end_rank = 0;
+
+        let mut cur = root;
+        while let Some(node) = cur {
+            let raw_node = unsafe { node.as_ref() };
+            if after_start(&raw_node.key) {
+                stack_left.push(node);
+                cur = raw_node.left;
+            } else {
+                start_rank += node_len(raw_node.left) + 1;
+                cur = raw_node.right;
+            }
+        }
+
+        let mut cur = root;
+        while let Some(node) = cur {
+            let raw_node = unsafe { node.as_ref() };
+            if before_end(&raw_node.key) {
+                stack_right.push(node);
+                end_rank += node_len(raw_node.left) + 1;
+                cur = raw_node.right;
+            } else {
+                cur = raw_node.left;
+            }
+        }
+
+        Self {
+            stack_left,
+            stack_right,
+            len: end_rank.saturating_sub(start_rank),
+            phantom: PhantomData,
+        }
+    }
+
+    fn next(&mut self) -> Option<NodePtr<T, A>> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+
+        let node = self.stack_left.pop()?;
+        let mut cur = unsafe { node.as_ref() }.right;
+        while let Some(n) = cur {
+            self.stack_left.push(n);
+            cur = unsafe { n.as_ref() }.left;
+        }
+        Some(node)
+    }
+
+    fn next_back(&mut self) -> Option<NodePtr<T, A>> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+
+        let node = self.stack_right.pop()?;
+        let mut cur = unsafe { node.as_ref() }.left;
+        while let Some(n) = cur {
+            self.stack_right.push(n);
+            cur = unsafe { n.as_ref() }.right;
+        }
+        Some(node)
+    }
+
+    /// skipを満たす要素が接頭辞となるように与え，前側をskipを満たさない最初の要素まで進める
+    /// NOTE: 読み飛ばす要素数Kに対してO(log K)
+    fn seek(&mut self, mut skip: impl FnMut(&T) -> bool) {
+        let mut skipped = 0;
+        while let Some(&node) = self.stack_left.last() {
+            let raw_node = unsafe { node.as_ref() };
+            if !skip(&raw_node.key) {
+                break;
+            }
+            self.stack_left.pop();
+            skipped += 1;
+
+            let mut cur = raw_node.right;
+            while let Some(n) = cur {
+                let raw_n = unsafe { n.as_ref() };
+                if skip(&raw_n.key) {
+                    skipped += node_len(raw_n.left) + 1;
+                    cur = raw_n.right;
+                } else {
+                    self.stack_left.push(n);
+                    cur = raw_n.left;
+                }
+            }
+        }
+        self.len = self.len.saturating_sub(skipped);
+    }
+
+    /// skipを満たす要素が接尾辞となるように与え，後側をskipを満たさない最後の要素まで戻す
+    /// NOTE: 読み飛ばす要素数Kに対してO(log K)
+    fn seek_back(&mut self, mut skip: impl FnMut(&T) -> bool) {
+        let mut skipped = 0;
+        while let Some(&node) = self.stack_right.last() {
+            let raw_node = unsafe { node.as_ref() };
+            if !skip(&raw_node.key) {
+                break;
+            }
+            self.stack_right.pop();
+            skipped += 1;
+
+            let mut cur = raw_node.left;
+            while let Some(n) = cur {
+                let raw_n = unsafe { n.as_ref() };
+                if skip(&raw_n.key) {
+                    skipped += node_len(raw_n.right) + 1;
+                    cur = raw_n.left;
+                } else {
+                    self.stack_right.push(n);
+                    cur = raw_n.right;
+                }
+            }
+        }
+        self.len = self.len.saturating_sub(skipped);
+    }
+}
+
+/// 範囲内の要素を昇順に返すイテレータ
+/// 構築時に範囲の両端を探索するのでO(log N)，1要素あたりならしO(1)
+pub struct RangeIter<'a, T>(NodeRange<'a, T>);
+
+impl<'a, T> RangeIter<'a, T> {
+    fn new<Q, R>(root: Link<T>, range: R) -> Self
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Self(NodeRange::new(
+            root,
+            |x| is_after_start(x.borrow(), range.start_bound()),
+            |x| is_before_end(x.borrow(), range.end_bound()),
+        ))
+    }
+}
+
+impl<'a, T: 'a> Iterator for RangeIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(&unsafe { self.0.next()?.as_ref() }.key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<'a, T: 'a> DoubleEndedIterator for RangeIter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        Some(&unsafe { self.0.next_back()?.as_ref() }.key)
+    }
+}
+
+impl<'a, T: 'a> ExactSizeIterator for RangeIter<'a, T> {}
+
+/// 要素数small, largeの2つの集合について
+/// smallの各要素でlargeを探索した方が2つを並行に走査するより速いか
+#[inline]
+fn is_lopsided(small: usize, large: usize) -> bool {
+    const THRESHOLD: usize = 16;
+    small.saturating_mul(THRESHOLD) < large
+}
+
+/// 前後両方から1要素ずつ先読みできるイテレータ
+struct SetIter<'a, T> {
+    iter: RangeIter<'a, T>,
+    front: Option<Option<&'a T>>,
+    back: Option<Option<&'a T>>,
+}
+
+impl<'a, T: Ord> SetIter<'a, T> {
+    fn new(iter: RangeIter<'a, T>) -> Self {
+        Self {
+            iter,
+            front: None,
+            back: None,
+        }
+    }
+
+    fn peek(&mut self) -> Option<&'a T> {
+        if self.front.is_none() {
+            // 前から取り尽くした場合は後ろで先読みした要素が残っている
+            let item = self.iter.next().or_else(|| self.back.take().flatten());
+            self.front = Some(item);
+        }
+        self.front.flatten()
+    }
+
+    fn peek_back(&mut self) -> Option<&'a T> {
+        if self.back.is_none() {
+            let item = self
+                .iter
+                .next_back()
+                .or_else(|| self.front.take().flatten());
+            self.back = Some(item);
+        }
+        self.back.flatten()
+    }
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.peek();
+        self.front.take().flatten()
+    }
+
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.peek_back();
+        self.back.take().flatten()
     }
 
-    fn push_left(&mut self, mut node: &'a Link<T>) {
-        while let Some(node_ptr) = node {
-            self.stack_left.push(node_ptr);
-            node = &unsafe { node_ptr.as_ref() }.left;
+    /// 前側をkey以上の最初の要素まで進める
+    fn seek(&mut self, key: &T) {
+        match self.front {
+            Some(Some(x)) if x >= key => return,
+            // 前から取り尽くしている
+            Some(None) => return,
+            _ => self.front = None,
         }
+        self.iter.0.seek(|x| x < key);
     }
 
-    fn push_right(&mut self, mut node: &'a Link<T>) {
-        while let Some(node_ptr) = node {
-            self.stack_right.push(node_ptr);
-            node = &unsafe { node_ptr.as_ref() }.right;
+    /// 後側をkey以下の最後の要素まで戻す
+    fn seek_back(&mut self, key: &T) {
+        match self.back {
+            Some(Some(x)) if x <= key => return,
+            Some(None) => return,
+            _ => self.back = None,
         }
+        self.iter.0.seek_back(|x| x > key);
     }
+}
 
-    fn in_range(&self, key: &T) -> bool {
-        match self.range.start_bound() {
-            Bound::Included(start) if key < start => return false,
-            Bound::Excluded(start) if key <= start => return false,
-            _ => {}
+pub struct Union<'a, T> {
+    a: SetIter<'a, T>,
+    b: SetIter<'a, T>,
+}
+
+impl<'a, T: Ord> Iterator for Union<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match (self.a.peek(), self.b.peek()) {
+            (Some(x), Some(y)) => match x.cmp(y) {
+                Ordering::Less => self.a.next(),
+                Ordering::Greater => self.b.next(),
+                Ordering::Equal => {
+                    self.b.next();
+                    self.a.next()
+                }
+            },
+            (Some(_), None) => self.a.next(),
+            (None, _) => self.b.next(),
         }
+    }
+}
 
-        match self.range.end_bound() {
-            Bound::Included(end) if key > end => return false,
-            Bound::Excluded(end) if key >= end => return false,
-            _ => {}
+impl<'a, T: Ord> DoubleEndedIterator for Union<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match (self.a.peek_back(), self.b.peek_back()) {
+            (Some(x), Some(y)) => match x.cmp(y) {
+                Ordering::Less => self.b.next_back(),
+                Ordering::Greater => self.a.next_back(),
+                Ordering::Equal => {
+                    self.b.next_back();
+                    self.a.next_back()
+                }
+            },
+            (Some(_), None) => self.a.next_back(),
+            (None, _) => self.b.next_back(),
+        }
+    }
+}
+
+enum IntersectionInner<'a, T, S: Storage> {
+    /// 2つの集合を並行に走査する
+    Stitch {
+        a: SetIter<'a, T>,
+        b: SetIter<'a, T>,
+    },
+    /// smallの各要素をlargeから探索する
+    Search {
+        small: RangeIter<'a, T>,
+        large: &'a AvlTreeSetIn<T, S>,
+    },
+}
+
+pub struct Intersection<'a, T, S: Storage = Boxed> {
+    inner: IntersectionInner<'a, T, S>,
+}
+
+impl<'a, T: Ord, S: Storage> Iterator for Intersection<'a, T, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            IntersectionInner::Stitch { a, b } => loop {
+                let (x, y) = (a.peek()?, b.peek()?);
+                match x.cmp(y) {
+                    Ordering::Less => a.seek(y),
+                    Ordering::Greater => b.seek(x),
+                    Ordering::Equal => {
+                        b.next();
+                        return a.next();
+                    }
+                }
+            },
+            IntersectionInner::Search { small, large } => small.find(|x| large.contains(x)),
+        }
+    }
+}
+
+impl<'a, T: Ord, S: Storage> DoubleEndedIterator for Intersection<'a, T, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            IntersectionInner::Stitch { a, b } => loop {
+                let (x, y) = (a.peek_back()?, b.peek_back()?);
+                match x.cmp(y) {
+                    Ordering::Less => b.seek_back(x),
+                    Ordering::Greater => a.seek_back(y),
+                    Ordering::Equal => {
+                        b.next_back();
+                        return a.next_back();
+                    }
+                }
+            },
+            IntersectionInner::Search { small, large } => small.rfind(|x| large.contains(x)),
+        }
+    }
+}
+
+enum DifferenceInner<'a, T, S: Storage> {
+    /// 2つの集合を並行に走査する
+    Stitch {
+        a: SetIter<'a, T>,
+        b: SetIter<'a, T>,
+    },
+    /// aの各要素をbから探索する
+    Search {
+        a: RangeIter<'a, T>,
+        b: &'a AvlTreeSetIn<T, S>,
+    },
+}
+
+pub struct Difference<'a, T, S: Storage = Boxed> {
+    inner: DifferenceInner<'a, T, S>,
+}
+
+impl<'a, T: Ord, S: Storage> Iterator for Difference<'a, T, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            DifferenceInner::Stitch { a, b } => loop {
+                let x = a.peek()?;
+                let Some(y) = b.peek() else {
+                    return a.next();
+                };
+                match x.cmp(y) {
+                    Ordering::Less => return a.next(),
+                    Ordering::Greater => b.seek(x),
+                    Ordering::Equal => {
+                        a.next();
+                        b.next();
+                    }
+                }
+            },
+            DifferenceInner::Search { a, b } => a.find(|x| !b.contains(x)),
         }
+    }
+}
 
-        true
+impl<'a, T: Ord, S: Storage> DoubleEndedIterator for Difference<'a, T, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            DifferenceInner::Stitch { a, b } => loop {
+                let x = a.peek_back()?;
+                let Some(y) = b.peek_back() else {
+                    return a.next_back();
+                };
+                match x.cmp(y) {
+                    Ordering::Less => b.seek_back(x),
+                    Ordering::Greater => return a.next_back(),
+                    Ordering::Equal => {
+                        a.next_back();
+                        b.next_back();
+                    }
+                }
+            },
+            DifferenceInner::Search { a, b } => a.rfind(|x| !b.contains(x)),
+        }
     }
 }
 
-impl<'a, T: Ord, B: RangeBounds<T>> Iterator for RangeIter<'a, T, B> {
+pub struct SymmetricDifference<'a, T> {
+    a: SetIter<'a, T>,
+    b: SetIter<'a, T>,
+}
+
+impl<'a, T: Ord> Iterator for SymmetricDifference<'a, T> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let node = unsafe { self.stack_left.pop()?.as_ref() };
-            self.push_left(&node.right);
-            if self.in_range(&node.key) {
-                return Some(&node.key);
+            match (self.a.peek(), self.b.peek()) {
+                (Some(x), Some(y)) => match x.cmp(y) {
+                    Ordering::Less => return self.a.next(),
+                    Ordering::Greater => return self.b.next(),
+                    Ordering::Equal => {
+                        self.a.next();
+                        self.b.next();
+                    }
+                },
+                (Some(_), None) => return self.a.next(),
+                (None, _) => return self.b.next(),
             }
         }
     }
 }
 
-impl<'a, T: Ord, B: RangeBounds<T>> DoubleEndedIterator for RangeIter<'a, T, B> {
+impl<'a, T: Ord> DoubleEndedIterator for SymmetricDifference<'a, T> {
     fn next_back(&mut self) -> Option<Self::Item> {
         loop {
-            let node = unsafe { self.stack_right.pop()?.as_ref() };
-            self.push_right(&node.left);
-            if self.in_range(&node.key) {
-                return Some(&node.key);
+            match (self.a.peek_back(), self.b.peek_back()) {
+                (Some(x), Some(y)) => match x.cmp(y) {
+                    Ordering::Less => return self.b.next_back(),
+                    Ordering::Greater => return self.a.next_back(),
+                    Ordering::Equal => {
+                        self.a.next_back();
+                        self.b.next_back();
+                    }
+                },
+                (Some(_), None) => return self.a.next_back(),
+                (None, _) => return self.b.next_back(),
             }
         }
     }
@@ -701,41 +2024,267 @@ fn visualize<T: Display>(root: Link<T>) {
         }
     }
 
-    let mut res = String::new();
-    visualize(root, "", true, true, &mut res);
-    println!("{}", res);
-}
+    let mut res = String::new();
+    visualize(root, "", true, true, &mut res);
+    println!("{}", res);
+}
+
+impl<T: Display, S: Storage> AvlTreeSetIn<T, S> {
+    #[allow(unused)]
+    pub fn visualize(&self) {
+        visualize(self.root);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{node_height, node_len, ArenaAvlTreeSet, AvlTreeSet, Link};
+
+    /// 1つ目の値だけで比較する型
+    /// 2つ目の値で等しい要素のどれが残ったかを区別する
+    #[derive(Debug)]
+    pub(crate) struct Item<U>(pub i32, pub U);
+
+    impl<U> PartialEq for Item<U> {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl<U> Eq for Item<U> {}
+
+    impl<U> PartialOrd for Item<U> {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl<U> Ord for Item<U> {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    #[test]
+    fn test_avl_tree_set_insert_and_contains() {
+        let mut tree = AvlTreeSet::new();
+        assert!(!tree.contains(&3));
+        assert!(!tree.contains(&1));
+        assert!(!tree.contains(&4));
+        assert!(!tree.contains(&5));
+        assert!(!tree.contains(&100));
+        assert!(tree.insert(3));
+        assert!(tree.insert(1));
+        assert!(tree.insert(4));
+        assert!(!tree.insert(1));
+        assert!(tree.insert(5));
+        assert!(tree.contains(&3));
+        assert!(tree.contains(&1));
+        assert!(tree.contains(&4));
+        assert!(tree.contains(&5));
+        assert!(!tree.contains(&100));
+    }
+
+    #[test]
+    fn test_avl_tree_set_clone() {
+        let mut tree1 = AvlTreeSet::from_iter((0..100).map(|x| x.to_string()));
+        let mut tree2 = tree1.clone();
+        assert_eq!(tree1, tree2);
+        assert!(is_balanced(tree2.root));
+
+        assert!(tree1.remove("10"));
+        assert!(tree1.insert("x".to_string()));
+        assert!(tree2.remove(&"20".to_string()));
+        assert!(tree2.insert("y".to_string()));
+        assert!(!tree1.contains("10"));
+        assert!(tree1.contains("20"));
+        assert!(tree2.contains(&"10".to_string()));
+        assert!(!tree2.contains(&"20".to_string()));
+        drop(tree1);
+        assert_eq!(tree2.len(), 100);
+        drop(tree2);
+
+        let src = AvlTreeSet::from_iter((0..50).map(|x| x.to_string()));
+        for n in [0, 10, 50, 200] {
+            let mut dst = AvlTreeSet::from_iter((0..n).map(|x| (x * 7).to_string()));
+            dst.clone_from(&src);
+            assert_eq!(dst, src);
+            assert!(is_balanced(dst.root));
+            dst.insert("z".to_string());
+            assert_ne!(dst, src);
+        }
+    }
+
+    #[test]
+    fn test_avl_tree_set_from_sorted() {
+        let tree = AvlTreeSet::from_sorted_vec(vec![1, 2, 2, 3, 5, 5, 5, 8]);
+        assert!(tree.iter().copied().eq([1, 2, 3, 5, 8]));
+        assert!(is_balanced(tree.root));
+
+        let tree = AvlTreeSet::from_sorted_iter(0..1000);
+        assert!(tree.iter().copied().eq(0..1000));
+        assert!(is_balanced(tree.root));
+        assert_eq!(node_height(tree.root), 10);
+
+        let tree = AvlTreeSet::<i32>::from_sorted_iter([]);
+        assert!(tree.is_empty());
+
+        // 等しい要素は最初に現れたものを残す
+        let tree = AvlTreeSet::from_iter([(1, "a"), (0, "b"), (1, "c")].map(|(x, c)| Item(x, c)));
+        assert!(tree.iter().map(|x| x.1).eq(["b", "a"]));
+        let tree =
+            AvlTreeSet::from_sorted_iter([(0, "a"), (0, "b"), (1, "c")].map(|(x, c)| Item(x, c)));
+        assert!(tree.iter().map(|x| x.1).eq(["a", "c"]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_avl_tree_set_from_sorted_unsorted() {
+        AvlTreeSet::from_sorted_vec(vec![1, 3, 2]);
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_from_iter_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = thread_rng();
+
+        for _ in 0..100 {
+            let mut v = (0..rng.gen_range(0..300))
+                .map(|_| rng.gen_range(-100..=100))
+                .collect::<Vec<i32>>();
+            if rng.gen_range(0..2) == 0 {
+                v.sort();
+            }
+            let tree = AvlTreeSet::from(v.clone());
+            assert!(is_balanced(tree.root));
+            assert!(tree.into_iter().eq(BTreeSet::from_iter(v)));
+        }
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_retain_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = thread_rng();
+
+        for _ in 0..100 {
+            let v = (0..rng.gen_range(0..300))
+                .map(|_| rng.gen_range(-500..=500))
+                .collect::<Vec<i32>>();
+            let m = rng.gen_range(1..=5);
+            let mut avl = AvlTreeSet::from(v.clone());
+            let mut b = BTreeSet::from_iter(v);
+            avl.retain(|x| x % m == 0);
+            b.retain(|x| x % m == 0);
+            assert!(is_balanced(avl.root));
+            assert!(avl.iter().eq(b.iter()));
+        }
+    }
+
+    #[test]
+    fn test_avl_tree_set_retain_panic() {
+        use std::{
+            panic::{catch_unwind, AssertUnwindSafe},
+            rc::Rc,
+        };
 
-impl<T: Display> AvlTreeSet<T> {
-    #[allow(unused)]
-    pub fn visualize(&self) {
-        visualize(self.root);
+        let rc = Rc::new(());
+        let mut tree = AvlTreeSet::from_iter((0..100).map(|i| (i, rc.clone())));
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            tree.retain(|&(i, _)| {
+                assert!(i < 50);
+                i % 2 == 0
+            })
+        }));
+        assert!(res.is_err());
+        // panicした要素以降は削除せずに残す
+        assert!(tree
+            .iter()
+            .map(|&(i, _)| i)
+            .eq((0..50).step_by(2).chain(50..100)));
+        assert!(is_balanced(tree.root));
+        assert_eq!(Rc::strong_count(&rc), 76);
+        drop(tree);
+        assert_eq!(Rc::strong_count(&rc), 1);
     }
-}
 
-#[cfg(test)]
-mod tests {
+    #[test]
+    fn test_avl_tree_set_extract_if() {
+        let mut tree = AvlTreeSet::from_iter(0..10);
+        assert!(tree.extract_if(|x| x % 3 == 0).eq([0, 3, 6, 9]));
+        assert!(tree.iter().copied().eq([1, 2, 4, 5, 7, 8]));
+
+        // 途中で破棄した場合は残りを削除しない
+        assert_eq!(tree.extract_if(|x| x % 2 == 0).next(), Some(2));
+        assert!(tree.iter().copied().eq([1, 4, 5, 7, 8]));
+        assert!(is_balanced(tree.root));
+
+        assert_eq!(tree.extract_if(|_| true).count(), 5);
+        assert!(tree.is_empty());
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_drain_range_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = thread_rng();
 
-    use super::AvlTreeSet;
+        for _ in 0..100 {
+            let v = (0..rng.gen_range(0..300))
+                .map(|_| rng.gen_range(-500..=500))
+                .collect::<Vec<i32>>();
+            let mut avl = AvlTreeSet::from(v.clone());
+            let mut b = BTreeSet::from_iter(v);
+            let l = rng.gen_range(-500..=500);
+            let r = rng.gen_range(l..=500);
+            let drained = b.range(l..=r).copied().collect::<Vec<_>>();
+            b.retain(|x| !(l..=r).contains(x));
+            assert!(avl.drain_range(l..=r).eq(drained));
+            assert!(is_balanced(avl.root));
+            assert!(avl.iter().eq(b.iter()));
+        }
+    }
 
     #[test]
-    fn test_avl_tree_set_insert_and_contains() {
-        let mut tree = AvlTreeSet::new();
-        assert!(!tree.contains(&3));
-        assert!(!tree.contains(&1));
-        assert!(!tree.contains(&4));
-        assert!(!tree.contains(&5));
-        assert!(!tree.contains(&100));
-        assert!(tree.insert(3));
-        assert!(tree.insert(1));
-        assert!(tree.insert(4));
-        assert!(!tree.insert(1));
-        assert!(tree.insert(5));
-        assert!(tree.contains(&3));
-        assert!(tree.contains(&1));
-        assert!(tree.contains(&4));
-        assert!(tree.contains(&5));
-        assert!(!tree.contains(&100));
+    fn test_avl_tree_set_borrow() {
+        use std::ops::Bound;
+
+        let mut st =
+            AvlTreeSet::from_iter(["apple", "banana", "cherry", "durian"].map(String::from));
+        assert!(st.contains("banana"));
+        assert!(!st.contains("blueberry"));
+        assert_eq!(st.get("cherry").map(String::as_str), Some("cherry"));
+        assert_eq!(st.get("coconut"), None);
+        assert!(st
+            .range::<str, _>((Bound::Included("b"), Bound::Excluded("d")))
+            .map(String::as_str)
+            .eq(["banana", "cherry"]));
+        assert_eq!(st.take("apple"), Some("apple".to_string()));
+        assert_eq!(st.take("apple"), None);
+        assert!(st.remove("banana"));
+        let rest = st.split_off("d");
+        assert!(st.iter().map(String::as_str).eq(["cherry"]));
+        assert!(rest.iter().map(String::as_str).eq(["durian"]));
+    }
+
+    #[test]
+    fn test_avl_tree_set_replace() {
+        let mut st = AvlTreeSet::from([Item(1, "a"), Item(2, "b")]);
+        assert_eq!(st.replace(Item(3, "c")).map(|x| x.1), None);
+        assert_eq!(st.replace(Item(1, "A")).map(|x| x.1), Some("a"));
+        assert!(st.iter().map(|x| x.1).eq(["A", "b", "c"]));
+        assert_eq!(st.len(), 3);
     }
 
     #[test]
@@ -807,6 +2356,228 @@ mod tests {
         assert!(tree2.is_empty());
     }
 
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_append_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = thread_rng();
+
+        for _ in 0..100 {
+            let (lo, hi) = (rng.gen_range(-500..=0), rng.gen_range(0..=500));
+            let v1 = (0..rng.gen_range(0..300))
+                .map(|_| rng.gen_range(lo..=hi))
+                .collect::<Vec<i32>>();
+            let (lo, hi) = (rng.gen_range(-500..=0), rng.gen_range(0..=500));
+            let v2 = (0..rng.gen_range(0..300))
+                .map(|_| rng.gen_range(lo..=hi))
+                .collect::<Vec<i32>>();
+            let mut avl1 = AvlTreeSet::from(v1.clone());
+            let mut avl2 = AvlTreeSet::from(v2.clone());
+            let mut b1 = BTreeSet::from_iter(v1);
+            let mut b2 = BTreeSet::from_iter(v2);
+            avl1.append(&mut avl2);
+            b1.append(&mut b2);
+            assert!(avl1.iter().eq(b1.iter()));
+            assert_eq!(avl1.len(), b1.len());
+            assert!(avl2.is_empty());
+            assert!(is_balanced(avl1.root));
+        }
+    }
+
+    #[test]
+    fn test_avl_tree_set_into_union() {
+        let tree1 = AvlTreeSet::from([1, 2, 3]);
+        let tree2 = AvlTreeSet::from_iter(10..100);
+        let tree = tree1.into_union(tree2);
+        assert!(tree.iter().copied().eq([1, 2, 3].into_iter().chain(10..100)));
+        assert!(is_balanced(tree.root));
+
+        let tree1 = AvlTreeSet::from_iter(10..100);
+        let tree2 = AvlTreeSet::from([1, 2, 3]);
+        let tree = tree1.into_union(tree2);
+        assert!(tree.iter().copied().eq([1, 2, 3].into_iter().chain(10..100)));
+        assert!(is_balanced(tree.root));
+
+        let tree1 = AvlTreeSet::from_iter((0..100).step_by(2));
+        let tree2 = AvlTreeSet::from_iter((0..100).step_by(3));
+        let tree = tree1.into_union(tree2);
+        assert!(tree
+            .iter()
+            .copied()
+            .eq((0..100).filter(|x| x % 2 == 0 || x % 3 == 0)));
+        assert!(is_balanced(tree.root));
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_set_operations() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = thread_rng();
+
+        for _ in 0..100 {
+            let v1 = (0..rng.gen_range(0..300))
+                .map(|_| rng.gen_range(-200..=200))
+                .collect::<Vec<i32>>();
+            let v2 = (0..rng.gen_range(0..20))
+                .map(|_| rng.gen_range(-200..=200))
+                .collect::<Vec<i32>>();
+            let (avl1, avl2) = (AvlTreeSet::from(v1.clone()), AvlTreeSet::from(v2.clone()));
+            let (b1, b2) = (BTreeSet::from_iter(v1), BTreeSet::from_iter(v2));
+
+            for (avl1, avl2, b1, b2) in [(&avl1, &avl2, &b1, &b2), (&avl2, &avl1, &b2, &b1)] {
+                assert!(avl1.union(avl2).eq(b1.union(b2)));
+                assert!(avl1.union(avl2).rev().eq(b1
+                    .union(b2)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev()));
+                assert!(avl1.intersection(avl2).eq(b1.intersection(b2)));
+                assert!(avl1.intersection(avl2).rev().eq(b1
+                    .intersection(b2)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev()));
+                assert!(avl1.difference(avl2).eq(b1.difference(b2)));
+                assert!(avl1.difference(avl2).rev().eq(b1
+                    .difference(b2)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev()));
+                assert!(avl1
+                    .symmetric_difference(avl2)
+                    .eq(b1.symmetric_difference(b2)));
+                assert!(avl1.symmetric_difference(avl2).rev().eq(b1
+                    .symmetric_difference(b2)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .rev()));
+            }
+        }
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_set_operations_double_ended() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        // 前後から交互に取り出しても各要素をちょうど1回ずつ返すか
+        fn check<'a>(
+            rng: &mut impl Rng,
+            mut iter: impl DoubleEndedIterator<Item = &'a i32>,
+            expected: Vec<&'a i32>,
+        ) {
+            let (mut front, mut back) = (vec![], vec![]);
+            loop {
+                let item = if rng.gen() {
+                    iter.next().map(|x| front.push(x))
+                } else {
+                    iter.next_back().map(|x| back.push(x))
+                };
+                if item.is_none() {
+                    break;
+                }
+            }
+            assert!(iter.next().is_none());
+            assert!(iter.next_back().is_none());
+            front.extend(back.into_iter().rev());
+            assert_eq!(front, expected);
+        }
+
+        let mut rng = thread_rng();
+
+        for _ in 0..100 {
+            let v1 = (0..rng.gen_range(0..300))
+                .map(|_| rng.gen_range(-200..=200))
+                .collect::<Vec<i32>>();
+            let v2 = (0..rng.gen_range(0..300))
+                .map(|_| rng.gen_range(-200..=200))
+                .collect::<Vec<i32>>();
+            let (avl1, avl2) = (AvlTreeSet::from(v1.clone()), AvlTreeSet::from(v2.clone()));
+            let (b1, b2) = (BTreeSet::from_iter(v1), BTreeSet::from_iter(v2));
+
+            for (avl1, avl2, b1, b2) in [(&avl1, &avl2, &b1, &b2), (&avl2, &avl1, &b2, &b1)] {
+                check(&mut rng, avl1.union(avl2), b1.union(b2).collect());
+                check(
+                    &mut rng,
+                    avl1.intersection(avl2),
+                    b1.intersection(b2).collect(),
+                );
+                check(&mut rng, avl1.difference(avl2), b1.difference(b2).collect());
+                check(
+                    &mut rng,
+                    avl1.symmetric_difference(avl2),
+                    b1.symmetric_difference(b2).collect(),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_avl_tree_set_subset_disjoint() {
+        let a = AvlTreeSet::from([2, 4, 6]);
+        let b = AvlTreeSet::from_iter(0..10);
+        let c = AvlTreeSet::from([1, 3, 5, 7, 9]);
+        let empty = AvlTreeSet::new();
+        assert!(a.is_subset(&b));
+        assert!(!b.is_subset(&a));
+        assert!(b.is_superset(&a));
+        assert!(!a.is_superset(&b));
+        assert!(!a.is_subset(&c));
+        assert!(a.is_disjoint(&c));
+        assert!(!a.is_disjoint(&b));
+        assert!(empty.is_subset(&a));
+        assert!(empty.is_subset(&empty));
+        assert!(a.is_superset(&empty));
+        assert!(empty.is_disjoint(&a));
+
+        let large = AvlTreeSet::from_iter((0..1000).step_by(2));
+        assert!(a.is_subset(&large));
+        assert!(!c.is_subset(&large));
+        assert!(c.is_disjoint(&large));
+        assert!(!AvlTreeSet::from([-2, 2]).is_subset(&large));
+        assert!(!AvlTreeSet::from([2, 1000]).is_subset(&large));
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_subset_disjoint_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = thread_rng();
+
+        for _ in 0..300 {
+            let v1 = (0..rng.gen_range(0..100))
+                .map(|_| rng.gen_range(-50..=50))
+                .collect::<Vec<i32>>();
+            // 半分の確率でv1の部分集合にする
+            let v2 = if rng.gen_bool(0.5) && !v1.is_empty() {
+                (0..rng.gen_range(0..10))
+                    .map(|_| v1[rng.gen_range(0..v1.len())])
+                    .collect::<Vec<i32>>()
+            } else {
+                (0..rng.gen_range(0..10))
+                    .map(|_| rng.gen_range(-50..=50))
+                    .collect::<Vec<i32>>()
+            };
+            let (avl1, avl2) = (AvlTreeSet::from(v1.clone()), AvlTreeSet::from(v2.clone()));
+            let (b1, b2) = (BTreeSet::from_iter(v1), BTreeSet::from_iter(v2));
+            assert_eq!(avl1.is_subset(&avl2), b1.is_subset(&b2));
+            assert_eq!(avl2.is_subset(&avl1), b2.is_subset(&b1));
+            assert_eq!(avl1.is_superset(&avl2), b1.is_superset(&b2));
+            assert_eq!(avl1.is_disjoint(&avl2), b1.is_disjoint(&b2));
+        }
+    }
+
     #[test]
     fn test_avl_tree_set_split_off() {
         let mut tree1 = AvlTreeSet::from([1, 2, 3, 4, 5, 6]);
@@ -829,12 +2600,64 @@ mod tests {
         assert!(tree.iter().copied().eq([10, 20]));
         assert!(tree2.iter().copied().eq([30, 40, 50]));
 
-        let mut tree1 = AvlTreeSet::new();
+        let mut tree1 = AvlTreeSet::<i32>::new();
         let tree2 = tree1.split_off(&10);
         assert!(tree1.is_empty());
         assert!(tree2.is_empty());
     }
 
+    #[test]
+    fn test_avl_tree_set_split_off_borrow() {
+        let mut tree1 = AvlTreeSet::from(["a", "bb", "c", "dd", "e"].map(String::from));
+        let tree2 = tree1.split_off("c");
+        assert!(tree1.iter().map(String::as_str).eq(["a", "bb"]));
+        assert!(tree2.iter().map(String::as_str).eq(["c", "dd", "e"]));
+
+        let key = String::from("bc");
+        let tree3 = tree1.split_off(key.as_str());
+        assert!(tree1.iter().map(String::as_str).eq(["a", "bb"]));
+        assert!(tree3.is_empty());
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_split_off_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = thread_rng();
+
+        for _ in 0..100 {
+            let v = (0..rng.gen_range(0..300))
+                .map(|_| rng.gen_range(-500..=500))
+                .collect::<Vec<i32>>();
+            let mut avl = AvlTreeSet::from(v.clone());
+            let mut b = BTreeSet::from_iter(v);
+            let x = rng.gen_range(-600..=600);
+            let avl2 = avl.split_off(&x);
+            let b2 = b.split_off(&x);
+            assert!(avl.iter().eq(b.iter()));
+            assert!(avl2.iter().eq(b2.iter()));
+            assert_eq!(avl.len(), b.len());
+            assert_eq!(avl2.len(), b2.len());
+            assert!(is_balanced(avl.root));
+            assert!(is_balanced(avl2.root));
+        }
+    }
+
+    /// 全てのノードでAVL木の条件が満たされているか
+    fn is_balanced<T>(node: Link<T>) -> bool {
+        node.is_none_or(|node| {
+            let node = unsafe { node.as_ref() };
+            (node_height(node.left) - node_height(node.right)).abs() <= 1
+                && node.height == node_height(node.left).max(node_height(node.right)) + 1
+                && node.len == node_len(node.left) + node_len(node.right) + 1
+                && is_balanced(node.left)
+                && is_balanced(node.right)
+        })
+    }
+
     #[test]
     fn test_avl_tree_set_range() {
         let st = AvlTreeSet::from([1, 2, 3, 4, 5]);
@@ -876,6 +2699,67 @@ mod tests {
         assert!(st.range(4..7).copied().eq([4, 6]));
     }
 
+    #[test]
+    fn test_avl_tree_set_range_double_ended() {
+        let st = AvlTreeSet::from_iter(0..10);
+        let mut iter = st.range(2..8);
+        assert_eq!(iter.len(), 6);
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next_back(), Some(&7));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next_back(), Some(&6));
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.next_back(), Some(&5));
+        assert_eq!(iter.next(), Some(&4));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+
+        let mut iter = st.iter();
+        assert_eq!(iter.next(), Some(&0));
+        assert_eq!(iter.nth_back(8), Some(&1));
+        assert_eq!(iter.next(), None);
+
+        #[allow(clippy::reversed_empty_ranges)]
+        let mut iter = st.range(5..3);
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_range_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = thread_rng();
+
+        for _ in 0..100 {
+            let v = (0..rng.gen_range(0..100))
+                .map(|_| rng.gen_range(-50..=50))
+                .collect::<Vec<i32>>();
+            let avl = AvlTreeSet::from(v.clone());
+            let b = BTreeSet::from_iter(v);
+            let x = rng.gen_range(-60..=60);
+            let y = rng.gen_range(x..=60);
+
+            let mut avl_iter = avl.range(x..=y);
+            let mut b_iter = b.range(x..=y);
+            loop {
+                let (p, q) = if rng.gen_bool(0.5) {
+                    (avl_iter.next(), b_iter.next())
+                } else {
+                    (avl_iter.next_back(), b_iter.next_back())
+                };
+                assert_eq!(p, q);
+                if p.is_none() {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::iter_nth_zero)]
     #[test]
     fn test_avl_tree_set_range_nth() {
         let st = AvlTreeSet::from([2, 4, 6, 8, 10, 12, 14, 16]);
@@ -892,6 +2776,56 @@ mod tests {
         assert_eq!(st.range(6..16).nth_back(4), Some(&6));
     }
 
+    #[test]
+    fn test_avl_tree_set_rank() {
+        let st = AvlTreeSet::from([2, 4, 6, 8, 10, 12, 14, 16]);
+        assert_eq!(st.rank(&1), 0);
+        assert_eq!(st.rank(&2), 0);
+        assert_eq!(st.rank(&3), 1);
+        assert_eq!(st.rank(&10), 4);
+        assert_eq!(st.rank(&17), 8);
+
+        assert_eq!(st.count_range(3..11), 4);
+        assert_eq!(st.count_range(4..=12), 5);
+        assert_eq!(st.count_range(..), 8);
+        assert_eq!(st.count_range(20..), 0);
+        assert_eq!(st.count_range(9..9), 0);
+
+        assert_eq!(st.nth_in_range(5.., 0), Some(&6));
+        assert_eq!(st.nth_in_range(5.., 4), Some(&14));
+        assert_eq!(st.nth_in_range(5.., 6), None);
+        assert_eq!(st.nth_in_range(4..=12, 4), Some(&12));
+        assert_eq!(st.nth_in_range(4..12, 4), None);
+        assert_eq!(st.nth_in_range(..7, 2), Some(&6));
+        assert_eq!(st.nth_in_range(..7, usize::MAX), None);
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_rank_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = thread_rng();
+
+        for _ in 0..100 {
+            let v = (0..rng.gen_range(0..100))
+                .map(|_| rng.gen_range(-50..=50))
+                .collect::<Vec<i32>>();
+            let avl = AvlTreeSet::from(v.clone());
+            let b = BTreeSet::from_iter(v);
+            let x = rng.gen_range(-60..=60);
+            let y = rng.gen_range(x..=60);
+            let k = rng.gen_range(0..20);
+            assert_eq!(avl.rank(&x), b.range(..x).count());
+            assert_eq!(avl.count_range(x..y), b.range(x..y).count());
+            assert_eq!(avl.count_range(x..=y), b.range(x..=y).count());
+            assert_eq!(avl.nth_in_range(x..y, k), b.range(x..y).nth(k));
+            assert_eq!(avl.nth_in_range(x.., k), b.range(x..).nth(k));
+        }
+    }
+
     #[test]
     fn test_avl_tree_set_lower_bound() {
         let st = AvlTreeSet::from([2, 4, 6, 8, 10, 12, 14, 16]);
@@ -901,6 +2835,151 @@ mod tests {
         assert_eq!(st.lower_bound(&17), None);
     }
 
+    #[test]
+    fn test_avl_tree_set_neighbors() {
+        let st = AvlTreeSet::from([2, 4, 6, 8]);
+        assert_eq!(st.upper_bound(&4), Some(&6));
+        assert_eq!(st.upper_bound(&5), Some(&6));
+        assert_eq!(st.upper_bound(&8), None);
+        assert_eq!(st.upper_bound(&0), Some(&2));
+        assert_eq!(st.floor(&4), Some(&4));
+        assert_eq!(st.floor(&5), Some(&4));
+        assert_eq!(st.floor(&1), None);
+        assert_eq!(st.floor(&100), Some(&8));
+        assert_eq!(st.predecessor(&4), Some(&2));
+        assert_eq!(st.predecessor(&5), Some(&4));
+        assert_eq!(st.predecessor(&2), None);
+        assert_eq!(st.first(), Some(&2));
+        assert_eq!(st.last(), Some(&8));
+
+        let st = AvlTreeSet::from(["apple".to_string(), "banana".to_string()]);
+        assert_eq!(st.lower_bound("b").map(|s| s.as_str()), Some("banana"));
+        assert_eq!(st.floor("b").map(|s| s.as_str()), Some("apple"));
+    }
+
+    #[test]
+    fn test_avl_tree_set_pop() {
+        let mut st = AvlTreeSet::from([3, 1, 4, 5, 9, 2, 6]);
+        assert_eq!(st.pop_first(), Some(1));
+        assert_eq!(st.pop_last(), Some(9));
+        assert_eq!(st.pop_first(), Some(2));
+        assert_eq!(st.pop_last(), Some(6));
+        assert!(st.iter().copied().eq([3, 4, 5]));
+        assert_eq!(st.pop_first(), Some(3));
+        assert_eq!(st.pop_first(), Some(4));
+        assert_eq!(st.pop_first(), Some(5));
+        assert_eq!(st.pop_first(), None);
+        assert_eq!(st.pop_last(), None);
+        assert_eq!(st.first(), None);
+        assert_eq!(st.last(), None);
+    }
+
+    #[test]
+    fn test_avl_tree_set_send_sync() {
+        use crate::{
+            cursor::{Cursor, CursorMut},
+            map, AvlIntervalTree, AvlTreeMap, AvlTreeMonoidMap, AvlTreeMultiSet, AvlTreeSetBy,
+            ExtractIf, IntoIter, Monoid, RangeIter,
+        };
+        use std::{cmp::Ordering, sync::Arc, thread};
+
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        struct Sum;
+        impl Monoid for Sum {
+            type S = i64;
+            fn identity() -> i64 {
+                0
+            }
+            fn op(a: &i64, b: &i64) -> i64 {
+                a + b
+            }
+        }
+
+        assert_send_sync::<AvlTreeSet<String>>();
+        assert_send_sync::<IntoIter<String>>();
+        assert_send_sync::<RangeIter<'_, String>>();
+        assert_send_sync::<ExtractIf<'_, String, fn(&String) -> bool>>();
+        assert_send_sync::<Cursor<'_, String>>();
+        assert_send_sync::<CursorMut<'_, String>>();
+        assert_send_sync::<AvlTreeMultiSet<String>>();
+        assert_send_sync::<AvlTreeSetBy<String, fn(&String, &String) -> Ordering>>();
+        assert_send_sync::<AvlTreeMap<String, Vec<i32>>>();
+        assert_send_sync::<map::RangeMut<'_, String, Vec<i32>>>();
+        assert_send_sync::<map::Entry<'_, String, Vec<i32>>>();
+        assert_send_sync::<AvlTreeMonoidMap<String, Sum>>();
+        assert_send_sync::<AvlIntervalTree<i32, String>>();
+
+        // 木を別スレッドに移動して変更し，共有して並行に読む
+        let mut st = AvlTreeSet::from_iter(0..100);
+        st = thread::spawn(move || {
+            st.retain(|x| x % 2 == 0);
+            st
+        })
+        .join()
+        .unwrap();
+        let st = Arc::new(st);
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let st = Arc::clone(&st);
+                thread::spawn(move || st.range(i * 10..).count())
+            })
+            .collect();
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.join().unwrap(), 50 - i * 5);
+        }
+        thread::scope(|s| {
+            let mut iter = st.iter();
+            s.spawn(move || assert_eq!(iter.next(), Some(&0)));
+        });
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_neighbors_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = thread_rng();
+
+        for _ in 0..5 {
+            let mut avl = AvlTreeSet::new();
+            let mut b = BTreeSet::new();
+            for _ in 0..1000 {
+                // 0: insert
+                // 1: neighbors
+                // 2: pop
+                let t = rng.gen_range(0..3);
+                match t {
+                    0 => {
+                        let x = rng.gen_range(-100..=100);
+                        assert_eq!(b.insert(x), avl.insert(x));
+                    }
+                    1 => {
+                        let x = rng.gen_range(-100..=100);
+                        assert_eq!(b.range(x..).next(), avl.lower_bound(&x));
+                        assert_eq!(b.range(x + 1..).next(), avl.upper_bound(&x));
+                        assert_eq!(b.range(..=x).next_back(), avl.floor(&x));
+                        assert_eq!(b.range(..x).next_back(), avl.predecessor(&x));
+                        assert_eq!(b.first(), avl.first());
+                        assert_eq!(b.last(), avl.last());
+                    }
+                    2 => {
+                        if rng.gen_bool(0.5) {
+                            assert_eq!(b.pop_first(), avl.pop_first());
+                        } else {
+                            assert_eq!(b.pop_last(), avl.pop_last());
+                        }
+                    }
+                    _ => {}
+                }
+                assert_eq!(b.len(), avl.len());
+            }
+            assert!(avl.into_iter().eq(b.into_iter()));
+        }
+    }
+
     #[allow(deprecated)]
     #[test]
     #[cfg_attr(miri, ignore)]
@@ -946,4 +3025,106 @@ mod tests {
             assert!(avl.into_iter().eq(b.into_iter()));
         }
     }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_arena_avl_tree_set_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = thread_rng();
+
+        for _ in 0..5 {
+            let mut avl = ArenaAvlTreeSet::new();
+            let mut b = BTreeSet::new();
+            for _ in 0..1000 {
+                // 0: insert
+                // 1: remove
+                // 2: split_off + append
+                // 3: extract_if
+                // 4: drain_range
+                // 5: cursor
+                // 6: clone_from
+                let t = rng.gen_range(0..7);
+                match t {
+                    0 => {
+                        let x = rng.gen_range(-100..=100).to_string();
+                        assert_eq!(b.insert(x.clone()), avl.insert(x));
+                    }
+                    1 => {
+                        let x = rng.gen_range(-100..=100).to_string();
+                        assert_eq!(b.take(&x), avl.take(x.as_str()));
+                    }
+                    2 => {
+                        let x = rng.gen_range(-100..=100).to_string();
+                        let mut avl_right = avl.split_off(&x);
+                        let mut b_right = b.split_off(&x);
+                        assert!(avl_right.iter().eq(b_right.iter()));
+                        avl_right.insert(x.clone());
+                        b_right.insert(x);
+                        avl.append(&mut avl_right);
+                        b.append(&mut b_right);
+                    }
+                    3 => {
+                        let x = rng.gen_range(1..5).to_string();
+                        let avl_removed = avl.extract_if(|y| y.ends_with(&x)).collect::<Vec<_>>();
+                        let b_removed = b.extract_if(.., |y| y.ends_with(&x)).collect::<Vec<_>>();
+                        assert_eq!(avl_removed, b_removed);
+                    }
+                    4 => {
+                        let l = rng.gen_range(-100..=100).to_string();
+                        let r = rng.gen_range(-100..=100).to_string();
+                        let (l, r) = if l <= r { (l, r) } else { (r, l) };
+                        let drained = avl.drain_range(l.clone()..r.clone()).collect::<Vec<_>>();
+                        let expected = b.range(l.clone()..r.clone()).cloned().collect::<Vec<_>>();
+                        b.retain(|x| !(l <= *x && *x < r));
+                        assert_eq!(drained, expected);
+                    }
+                    5 => {
+                        let x = rng.gen_range(-100..=100).to_string();
+                        let mut cursor = avl.lower_bound_cursor_mut(&x);
+                        if cursor.current() == Some(&x) {
+                            assert_eq!(cursor.remove_current(), Some(x.clone()));
+                            b.remove(&x);
+                        } else {
+                            cursor.insert_before(x.clone()).unwrap();
+                            b.insert(x);
+                        }
+                    }
+                    6 => {
+                        let mut cloned =
+                            ArenaAvlTreeSet::from_sorted_iter(b.iter().take(50).cloned());
+                        cloned.clone_from(&avl);
+                        assert!(cloned.iter().eq(b.iter()));
+                    }
+                    _ => {}
+                }
+                assert_eq!(b.len(), avl.len());
+                assert!(avl.iter().eq(b.iter()));
+                assert!(avl.iter().rev().eq(b.iter().rev()));
+            }
+            assert!(avl.into_iter().eq(b.into_iter()));
+        }
+    }
+
+    #[test]
+    fn test_arena_avl_tree_set_drop() {
+        use std::rc::Rc;
+
+        let rc = Rc::new(());
+        let mut tree = ArenaAvlTreeSet::from_iter((0..100).map(|i| (i, rc.clone())));
+        let mut right = tree.split_off(&(50, rc.clone()));
+        assert_eq!(Rc::strong_count(&rc), 101);
+        tree.retain(|(i, _)| i % 2 == 0);
+        assert_eq!(Rc::strong_count(&rc), 76);
+        let mut iter = right.drain_range((60, rc.clone())..);
+        iter.next();
+        drop(iter);
+        assert_eq!(Rc::strong_count(&rc), 36);
+        tree.append(&mut right);
+        assert_eq!(tree.len(), 35);
+        tree.clear();
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
 }
@@ -0,0 +1,710 @@
+//! AVL木によるordered mapの実装
+//! ノードとして`Node<(K, V)>`を用い，回転・平衡・連結はordered setと共通のものを使う
+
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt::Debug,
+    marker::PhantomData,
+    ops::{Index, RangeBounds},
+    ptr::NonNull,
+};
+
+use crate::{
+    balance, clone_tree, clone_tree_from, free, is_after_start, is_before_end, merge, node_len,
+    traverse_postorder, BoxPool, IntoIter, Link, Node, NodeRange,
+};
+
+type LinkPtr<K, V> = NonNull<Link<(K, V)>>;
+
+/// keyを探索し，根から辿ったリンクの列を返す
+/// 最後のリンクはkeyと等しいノードを指すか，keyを挿入すべき空のリンクである
+fn search_path<K, V, Q>(root: LinkPtr<K, V>, key: &Q) -> Vec<LinkPtr<K, V>>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    let mut path = vec![root];
+    let mut cur = root;
+    while let Some(mut node) = unsafe { *cur.as_ref() } {
+        let raw_node = unsafe { node.as_mut() };
+        cur = match key.cmp(raw_node.key.0.borrow()) {
+            Ordering::Equal => break,
+            Ordering::Less => NonNull::from(&mut raw_node.left),
+            Ordering::Greater => NonNull::from(&mut raw_node.right),
+        };
+        path.push(cur);
+    }
+    path
+}
+
+/// 経路上のリンクを下から順に平衡させる
+fn balance_path<K, V>(path: &[LinkPtr<K, V>]) {
+    for link in path.iter().rev() {
+        balance(unsafe { &mut *link.as_ptr() });
+    }
+}
+
+/// 経路の末尾が指すノードを木から切り離して返す
+fn remove_at<K, V>(path: &[LinkPtr<K, V>]) -> (K, V) {
+    let mut link = *path.last().unwrap();
+    let mut node = unsafe { link.as_ref() }.unwrap();
+    let (left, right) = {
+        let raw_node = unsafe { node.as_mut() };
+        (raw_node.left.take(), raw_node.right.take())
+    };
+    unsafe { *link.as_mut() = merge(left, right) };
+    balance_path(path);
+    let boxed = unsafe { Box::from_raw(node.as_ptr()) };
+    boxed.key
+}
+
+/// keyと等しいキーを持つノード
+fn find<K, V, Q>(root: Link<(K, V)>, key: &Q) -> Link<(K, V)>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    let mut cur = root;
+    while let Some(node) = cur {
+        let raw_node = unsafe { node.as_ref() };
+        cur = match key.cmp(raw_node.key.0.borrow()) {
+            Ordering::Equal => return Some(node),
+            Ordering::Less => raw_node.left,
+            Ordering::Greater => raw_node.right,
+        };
+    }
+    None
+}
+
+/// 昇順n番目のノード
+fn nth<K, V>(root: Link<(K, V)>, mut n: usize) -> Link<(K, V)> {
+    let mut cur = root;
+    while let Some(node) = cur {
+        let raw_node = unsafe { node.as_ref() };
+        let left_len = node_len(raw_node.left);
+        if n == left_len {
+            return Some(node);
+        } else if n < left_len {
+            cur = raw_node.left;
+        } else {
+            cur = raw_node.right;
+            n -= left_len + 1;
+        }
+    }
+    None
+}
+
+/// AVL木によるordered mapの実装
+pub struct AvlTreeMap<K, V> {
+    root: Link<(K, V)>,
+    phantom: PhantomData<Box<Node<(K, V)>>>,
+}
+
+unsafe impl<K: Send, V: Send> Send for AvlTreeMap<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for AvlTreeMap<K, V> {}
+
+impl<K, V> AvlTreeMap<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn len(&self) -> usize {
+        node_len(self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// keyに対応する値を挿入する
+    /// すでにkeyが存在する場合は値を置き換えて古い値を返す
+    pub fn insert(&mut self, key: K, value: V) -> Option<V>
+    where
+        K: Ord,
+    {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        find(self.root, key).is_some()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (k, v) = &unsafe { find(self.root, key)?.as_ref() }.key;
+        Some((k, v))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Some(&mut unsafe { find(self.root, key)?.as_mut() }.key.1)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let path = search_path(NonNull::from(&mut self.root), key);
+        let link = *path.last().unwrap();
+        unsafe { link.as_ref() }.is_some().then(|| remove_at(&path))
+    }
+
+    /// keyに対応するエントリを取得する
+    /// 根からの探索は1回だけ行う
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V>
+    where
+        K: Ord,
+    {
+        let path = search_path(NonNull::from(&mut self.root), &key);
+        let link = *path.last().unwrap();
+        if unsafe { link.as_ref() }.is_some() {
+            Entry::Occupied(OccupiedEntry {
+                path,
+                phantom: PhantomData,
+            })
+        } else {
+            Entry::Vacant(VacantEntry {
+                key,
+                path,
+                phantom: PhantomData,
+            })
+        }
+    }
+
+    /// キーの昇順n番目の要素
+    pub fn get_nth(&self, n: usize) -> Option<(&K, &V)> {
+        let (k, v) = &unsafe { nth(self.root, n)?.as_ref() }.key;
+        Some((k, v))
+    }
+
+    /// キーの昇順n番目の要素
+    pub fn get_nth_mut(&mut self, n: usize) -> Option<(&K, &mut V)> {
+        let (k, v) = &mut unsafe { nth(self.root, n)?.as_mut() }.key;
+        Some((k, v))
+    }
+
+    /// キーの降順n番目の要素
+    pub fn get_nth_back(&self, n: usize) -> Option<(&K, &V)> {
+        self.get_nth(self.len().checked_sub(n + 1)?)
+    }
+
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Range(node_range(self.root, range))
+    }
+
+    pub fn range_mut<Q, R>(&mut self, range: R) -> RangeMut<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        RangeMut(node_range(self.root, range), PhantomData)
+    }
+
+    pub fn iter(&self) -> Range<'_, K, V> {
+        Range(NodeRange::new(self.root, |_| true, |_| true))
+    }
+
+    pub fn iter_mut(&mut self) -> RangeMut<'_, K, V> {
+        RangeMut(NodeRange::new(self.root, |_| true, |_| true), PhantomData)
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    pub fn values_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut V> {
+        self.iter_mut().map(|(_, v)| v)
+    }
+}
+
+fn node_range<'a, K, V, Q, R>(root: Link<(K, V)>, range: R) -> NodeRange<'a, (K, V)>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    NodeRange::new(
+        root,
+        |(k, _)| is_after_start(k.borrow(), range.start_bound()),
+        |(k, _)| is_before_end(k.borrow(), range.end_bound()),
+    )
+}
+
+impl<K, V> Drop for AvlTreeMap<K, V> {
+    fn drop(&mut self) {
+        traverse_postorder(self.root, free);
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for AvlTreeMap<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            root: clone_tree(self.root, &mut BoxPool),
+            phantom: PhantomData,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        clone_tree_from(&mut self.root, source.root, &mut BoxPool);
+    }
+}
+
+impl<K, V> Default for AvlTreeMap<K, V> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<K: PartialEq, V: PartialEq> PartialEq for AvlTreeMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for AvlTreeMap<K, V> {}
+
+impl<K: Debug, V: Debug> Debug for AvlTreeMap<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, Q> Index<&Q> for AvlTreeMap<K, V>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &Self::Output {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a AvlTreeMap<K, V> {
+    type IntoIter = Range<'a, K, V>;
+    type Item = (&'a K, &'a V);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a mut AvlTreeMap<K, V> {
+    type IntoIter = RangeMut<'a, K, V>;
+    type Item = (&'a K, &'a mut V);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V> IntoIterator for AvlTreeMap<K, V> {
+    type IntoIter = IntoIter<(K, V)>;
+    type Item = (K, V);
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        IntoIter::new(self.root.take(), BoxPool)
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for AvlTreeMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|(k, v)| {
+            self.insert(k, v);
+        });
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for AvlTreeMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut res = Self::new();
+        res.extend(iter);
+        res
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for AvlTreeMap<K, V> {
+    fn from(v: [(K, V); N]) -> Self {
+        Self::from_iter(v)
+    }
+}
+
+pub struct Range<'a, K, V>(NodeRange<'a, (K, V)>);
+
+impl<'a, K: 'a, V: 'a> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = &unsafe { self.0.next()?.as_ref() }.key;
+        Some((k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<'a, K: 'a, V: 'a> DoubleEndedIterator for Range<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (k, v) = &unsafe { self.0.next_back()?.as_ref() }.key;
+        Some((k, v))
+    }
+}
+
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for Range<'a, K, V> {}
+
+pub struct RangeMut<'a, K, V>(NodeRange<'a, (K, V)>, PhantomData<&'a mut V>);
+
+// NOTE: キーは共有参照，値は可変参照として扱う
+unsafe impl<K: Sync, V: Send> Send for RangeMut<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for RangeMut<'_, K, V> {}
+
+impl<'a, K: 'a, V: 'a> Iterator for RangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = &mut unsafe { self.0.next()?.as_mut() }.key;
+        Some((k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<'a, K: 'a, V: 'a> DoubleEndedIterator for RangeMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (k, v) = &mut unsafe { self.0.next_back()?.as_mut() }.key;
+        Some((k, v))
+    }
+}
+
+impl<'a, K: 'a, V: 'a> ExactSizeIterator for RangeMut<'a, K, V> {}
+
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+impl<'a, K: Ord, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_insert_with_key(self, default: impl FnOnce(&K) -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify(self, f: impl FnOnce(&mut V)) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+/// すでに存在するキーのエントリ
+pub struct OccupiedEntry<'a, K, V> {
+    /// 根からエントリのノードまでのリンクの列
+    path: Vec<LinkPtr<K, V>>,
+    phantom: PhantomData<&'a mut AvlTreeMap<K, V>>,
+}
+
+unsafe impl<K: Send, V: Send> Send for OccupiedEntry<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for OccupiedEntry<'_, K, V> {}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    fn node(&self) -> NonNull<Node<(K, V)>> {
+        unsafe { self.path.last().unwrap().as_ref() }.unwrap()
+    }
+
+    pub fn key(&self) -> &K {
+        &unsafe { self.node().as_ref() }.key.0
+    }
+
+    pub fn get(&self) -> &V {
+        &unsafe { self.node().as_ref() }.key.1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut unsafe { self.node().as_mut() }.key.1
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut unsafe { self.node().as_mut() }.key.1
+    }
+
+    /// 値を置き換えて古い値を返す
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        remove_at(&self.path)
+    }
+}
+
+/// 存在しないキーのエントリ
+pub struct VacantEntry<'a, K, V> {
+    key: K,
+    /// 根からキーを挿入すべき空のリンクまでのリンクの列
+    path: Vec<LinkPtr<K, V>>,
+    phantom: PhantomData<&'a mut AvlTreeMap<K, V>>,
+}
+
+unsafe impl<K: Send, V: Send> Send for VacantEntry<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for VacantEntry<'_, K, V> {}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// 値を挿入して，その値への参照を返す
+    pub fn insert(self, value: V) -> &'a mut V {
+        let mut node = Node::new((self.key, value));
+        let mut link = *self.path.last().unwrap();
+        unsafe { *link.as_mut() = Some(node) };
+        balance_path(&self.path);
+        &mut unsafe { node.as_mut() }.key.1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AvlTreeMap, Entry};
+
+    #[test]
+    fn test_avl_tree_map_insert_and_get() {
+        let mut map = AvlTreeMap::new();
+        assert_eq!(map.insert(3, "c"), None);
+        assert_eq!(map.insert(1, "a"), None);
+        assert_eq!(map.insert(4, "d"), None);
+        assert_eq!(map.insert(1, "A"), Some("a"));
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&1), Some(&"A"));
+        assert_eq!(map.get(&2), None);
+        assert_eq!(map[&3], "c");
+        *map.get_mut(&4).unwrap() = "D";
+        assert!(map.iter().eq([(&1, &"A"), (&3, &"c"), (&4, &"D")]));
+        assert_eq!(map.remove(&3), Some("c"));
+        assert_eq!(map.remove(&3), None);
+        assert!(map.keys().copied().eq([1, 4]));
+    }
+
+    #[test]
+    fn test_avl_tree_map_borrow() {
+        let mut map = AvlTreeMap::new();
+        map.insert("apple".to_string(), 1);
+        map.insert("banana".to_string(), 2);
+        assert_eq!(map.get("apple"), Some(&1));
+        assert!(map.contains_key("banana"));
+        assert_eq!(map.remove("apple"), Some(1));
+        assert!(!map.contains_key("apple"));
+    }
+
+    #[test]
+    fn test_avl_tree_map_get_nth() {
+        let map = AvlTreeMap::from([(5, 'e'), (1, 'a'), (3, 'c')]);
+        assert_eq!(map.get_nth(0), Some((&1, &'a')));
+        assert_eq!(map.get_nth(1), Some((&3, &'c')));
+        assert_eq!(map.get_nth(2), Some((&5, &'e')));
+        assert_eq!(map.get_nth(3), None);
+        assert_eq!(map.get_nth_back(0), Some((&5, &'e')));
+        assert_eq!(map.get_nth_back(3), None);
+    }
+
+    #[test]
+    fn test_avl_tree_map_range() {
+        let mut map = AvlTreeMap::from_iter((0..10).map(|i| (i * 2, i)));
+        assert!(map.range(3..9).map(|(k, _)| *k).eq([4, 6, 8]));
+        assert!(map.range(3..9).rev().map(|(k, _)| *k).eq([8, 6, 4]));
+        assert!(map.range(..=4).map(|(k, _)| *k).eq([0, 2, 4]));
+        assert!(map.range(15..).map(|(k, _)| *k).eq([16, 18]));
+        assert_eq!(map.range(3..9).len(), 3);
+        assert_eq!(map.range(30..).next(), None);
+
+        for (_, v) in map.range_mut(4..=8) {
+            *v *= 10;
+        }
+        assert!(map.values().copied().eq([0, 1, 20, 30, 40, 5, 6, 7, 8, 9]));
+
+        let mut iter = map.range(2..12);
+        assert_eq!(iter.next(), Some((&2, &1)));
+        assert_eq!(iter.next_back(), Some((&10, &5)));
+        assert_eq!(iter.next(), Some((&4, &20)));
+        assert_eq!(iter.next_back(), Some((&8, &40)));
+        assert_eq!(iter.next(), Some((&6, &30)));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_avl_tree_map_entry() {
+        let mut map = AvlTreeMap::new();
+        for c in "abracadabra".chars() {
+            *map.entry(c).or_insert(0) += 1;
+        }
+        assert!(map
+            .iter()
+            .eq([(&'a', &5), (&'b', &2), (&'c', &1), (&'d', &1), (&'r', &2)]));
+
+        map.entry('a').and_modify(|v| *v = 100).or_insert(0);
+        map.entry('z')
+            .and_modify(|v| *v = 100)
+            .or_insert_with(|| 26);
+        assert_eq!(map.get(&'a'), Some(&100));
+        assert_eq!(map.get(&'z'), Some(&26));
+
+        match map.entry('b') {
+            Entry::Occupied(entry) => assert_eq!(entry.remove_entry(), ('b', 2)),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match map.entry('b') {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), 'b'),
+        }
+        assert!(!map.contains_key(&'b'));
+        assert_eq!(map.len(), 5);
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_map_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeMap;
+
+        let mut rng = thread_rng();
+
+        for _ in 0..5 {
+            let mut avl = AvlTreeMap::new();
+            let mut b = BTreeMap::new();
+            for _ in 0..1000 {
+                // 0: insert
+                // 1: get
+                // 2: remove
+                // 3: entry
+                // 4: range
+                let t = rng.gen_range(0..5);
+                let x = rng.gen_range(-100..=100);
+                match t {
+                    0 => {
+                        let v = rng.gen_range(0..100);
+                        assert_eq!(b.insert(x, v), avl.insert(x, v));
+                    }
+                    1 => {
+                        assert_eq!(b.get(&x), avl.get(&x));
+                    }
+                    2 => {
+                        assert_eq!(b.remove(&x), avl.remove(&x));
+                    }
+                    3 => {
+                        let v = rng.gen_range(0..100);
+                        *b.entry(x).and_modify(|e| *e += v).or_insert(v) *= 2;
+                        *avl.entry(x).and_modify(|e| *e += v).or_insert(v) *= 2;
+                    }
+                    4 => {
+                        let y = rng.gen_range(x..=100);
+                        assert!(avl.range(x..y).eq(b.range(x..y)));
+                        assert!(avl.range(x..=y).rev().eq(b.range(x..=y).rev()));
+                    }
+                    _ => {}
+                }
+                assert_eq!(b.len(), avl.len());
+                assert!(avl.iter().eq(b.iter()));
+            }
+            assert!(avl.into_iter().eq(b.into_iter()));
+        }
+    }
+}
@@ -0,0 +1,583 @@
+//! 値をモノイドとして扱い，キーの範囲に対する値の総積を求められるordered mapの実装
+//! 各ノードは部分木の値の総積を持ち，Node::fetchで長さ・高さとともに再計算する
+
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt::Debug,
+    marker::PhantomData,
+    mem::replace,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::{
+    clone_tree, clone_tree_from, free, is_after_start, is_before_end, merge, merge_with_root,
+    node_len, split_by, traverse_postorder, Augment, BoxPool, Link, Node, NodeRange,
+};
+
+/// 結合的な二項演算opと単位元identityを持つ集合
+pub trait Monoid {
+    type S: Clone;
+    fn identity() -> Self::S;
+    fn op(a: &Self::S, b: &Self::S) -> Self::S;
+}
+
+/// 部分木の値の総積
+struct Fold<M: Monoid>(M::S);
+
+impl<K, M: Monoid> Augment<(K, M::S)> for Fold<M> {
+    fn fetch(key: &(K, M::S), left: Option<&Self>, right: Option<&Self>) -> Self {
+        let res = match left {
+            Some(left) => M::op(&left.0, &key.1),
+            None => key.1.clone(),
+        };
+        match right {
+            Some(right) => Fold(M::op(&res, &right.0)),
+            None => Fold(res),
+        }
+    }
+}
+
+impl<M: Monoid> Clone for Fold<M> {
+    fn clone(&self) -> Self {
+        Fold(self.0.clone())
+    }
+}
+
+type MonoidNode<K, M> = Node<(K, <M as Monoid>::S), Fold<M>>;
+type MonoidLink<K, M> = Link<(K, <M as Monoid>::S), Fold<M>>;
+
+/// 部分木全体の総積
+fn fold_all<K, M: Monoid>(node: MonoidLink<K, M>) -> M::S {
+    node.map_or_else(M::identity, |node| unsafe { node.as_ref() }.aug.0.clone())
+}
+
+/// 部分木のうち下限を満たす要素の総積
+fn fold_from<K, M, Q>(node: MonoidLink<K, M>, start: Bound<&Q>) -> M::S
+where
+    K: Borrow<Q>,
+    M: Monoid,
+    Q: Ord + ?Sized,
+{
+    let Some(node) = node.map(|node| unsafe { node.as_ref() }) else {
+        return M::identity();
+    };
+    if is_after_start(node.key.0.borrow(), start) {
+        let left = M::op(&fold_from::<K, M, Q>(node.left, start), &node.key.1);
+        M::op(&left, &fold_all::<K, M>(node.right))
+    } else {
+        fold_from::<K, M, Q>(node.right, start)
+    }
+}
+
+/// 部分木のうち上限を満たす要素の総積
+fn fold_to<K, M, Q>(node: MonoidLink<K, M>, end: Bound<&Q>) -> M::S
+where
+    K: Borrow<Q>,
+    M: Monoid,
+    Q: Ord + ?Sized,
+{
+    let Some(node) = node.map(|node| unsafe { node.as_ref() }) else {
+        return M::identity();
+    };
+    if is_before_end(node.key.0.borrow(), end) {
+        let right = M::op(&node.key.1, &fold_to::<K, M, Q>(node.right, end));
+        M::op(&fold_all::<K, M>(node.left), &right)
+    } else {
+        fold_to::<K, M, Q>(node.left, end)
+    }
+}
+
+/// 部分木の要素を昇順にaccへ畳み込んでいき，fがfalseとなる最初のノードを返す
+/// 部分木全体でfがtrueの場合はaccに部分木全体を畳み込んでNoneを返す
+fn first_fail<K, M: Monoid>(
+    mut node: MonoidLink<K, M>,
+    acc: &mut M::S,
+    f: &impl Fn(&M::S) -> bool,
+) -> MonoidLink<K, M> {
+    let all = M::op(acc, &fold_all::<K, M>(node));
+    if f(&all) {
+        *acc = all;
+        return None;
+    }
+    while let Some(raw_node) = node.map(|node| unsafe { node.as_ref() }) {
+        let left = M::op(acc, &fold_all::<K, M>(raw_node.left));
+        if !f(&left) {
+            node = raw_node.left;
+            continue;
+        }
+        let mid = M::op(&left, &raw_node.key.1);
+        if !f(&mid) {
+            *acc = left;
+            return node;
+        }
+        *acc = mid;
+        node = raw_node.right;
+    }
+    unreachable!()
+}
+
+/// 部分木の要素を降順にaccへ畳み込んでいき，fがfalseとなる最初のノードを返す
+/// 部分木全体でfがtrueの場合はaccに部分木全体を畳み込んでNoneを返す
+fn last_fail<K, M: Monoid>(
+    mut node: MonoidLink<K, M>,
+    acc: &mut M::S,
+    f: &impl Fn(&M::S) -> bool,
+) -> MonoidLink<K, M> {
+    let all = M::op(&fold_all::<K, M>(node), acc);
+    if f(&all) {
+        *acc = all;
+        return None;
+    }
+    while let Some(raw_node) = node.map(|node| unsafe { node.as_ref() }) {
+        let right = M::op(&fold_all::<K, M>(raw_node.right), acc);
+        if !f(&right) {
+            node = raw_node.right;
+            continue;
+        }
+        let mid = M::op(&raw_node.key.1, &right);
+        if !f(&mid) {
+            *acc = right;
+            return node;
+        }
+        *acc = mid;
+        node = raw_node.left;
+    }
+    unreachable!()
+}
+
+/// キーの範囲に対する値の総積を求められるordered map
+/// 値はモノイドMの元として扱う
+pub struct AvlTreeMonoidMap<K, M: Monoid> {
+    root: MonoidLink<K, M>,
+    phantom: PhantomData<Box<MonoidNode<K, M>>>,
+}
+
+unsafe impl<K: Send, M: Monoid> Send for AvlTreeMonoidMap<K, M> where M::S: Send {}
+unsafe impl<K: Sync, M: Monoid> Sync for AvlTreeMonoidMap<K, M> where M::S: Sync {}
+
+impl<K, M: Monoid> AvlTreeMonoidMap<K, M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn len(&self) -> usize {
+        node_len(self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// keyより小さい要素からなる木，keyと等しいノード，keyより大きい要素からなる木に分割する
+    fn split3<Q>(&mut self, key: &Q) -> [MonoidLink<K, M>; 3]
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (less, rest) = split_by(self.root.take(), &mut |x: &(K, M::S)| x.0.borrow() < key);
+        let (equal, greater) = split_by(rest, &mut |x: &(K, M::S)| x.0.borrow() <= key);
+        [less, equal, greater]
+    }
+
+    /// keyに対応する値を挿入する
+    /// すでにkeyが存在する場合は値を置き換えて古い値を返す
+    /// NOTE: O(log N)
+    pub fn insert(&mut self, key: K, value: M::S) -> Option<M::S>
+    where
+        K: Ord,
+    {
+        let [less, equal, greater] = self.split3(&key);
+        let (node, res) = match equal {
+            Some(mut node) => {
+                let raw_node = unsafe { node.as_mut() };
+                let old = replace(&mut raw_node.key.1, value);
+                raw_node.fetch();
+                (node, Some(old))
+            }
+            None => (Node::new((key, value)), None),
+        };
+        self.root = merge_with_root(less, node, greater);
+        res
+    }
+
+    /// keyに対応する値を削除して返す
+    /// NOTE: O(log N)
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<M::S>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let [less, equal, greater] = self.split3(key);
+        self.root = merge(less, greater);
+        equal.map(|node| {
+            let boxed = unsafe { Box::from_raw(node.as_ptr()) };
+            boxed.key.1
+        })
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&M::S>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut cur = self.root;
+        while let Some(node) = cur.map(|node| unsafe { node.as_ref() }) {
+            cur = match key.cmp(node.key.0.borrow()) {
+                Ordering::Equal => return Some(&node.key.1),
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right,
+            };
+        }
+        None
+    }
+
+    /// キーが範囲内にある値を昇順に演算した結果
+    /// NOTE: O(log N)
+    pub fn fold<Q, R>(&self, range: R) -> M::S
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut cur = self.root;
+        while let Some(node) = cur.map(|node| unsafe { node.as_ref() }) {
+            let key = node.key.0.borrow();
+            if !is_after_start(key, range.start_bound()) {
+                cur = node.right;
+            } else if !is_before_end(key, range.end_bound()) {
+                cur = node.left;
+            } else {
+                let left = fold_from::<K, M, Q>(node.left, range.start_bound());
+                let right = fold_to::<K, M, Q>(node.right, range.end_bound());
+                return M::op(&M::op(&left, &node.key.1), &right);
+            }
+        }
+        M::identity()
+    }
+
+    /// start以上のキーを昇順に見ていき，fold(start..k)に対してfがtrueとなる最大のkを返す
+    /// すべてのキーについてtrueとなる場合はNone
+    /// fは単位元に対してtrueで，範囲を広げるとtrueからfalseに一度だけ変わるものに限る
+    /// NOTE: O(log N)
+    pub fn max_right<Q>(&self, start: &Q, f: impl Fn(&M::S) -> bool) -> Option<&K>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        fn max_right<K, M, Q>(
+            node: MonoidLink<K, M>,
+            start: &Q,
+            acc: &mut M::S,
+            f: &impl Fn(&M::S) -> bool,
+        ) -> MonoidLink<K, M>
+        where
+            K: Borrow<Q>,
+            M: Monoid,
+            Q: Ord + ?Sized,
+        {
+            let raw_node = unsafe { node?.as_ref() };
+            if raw_node.key.0.borrow() < start {
+                return max_right(raw_node.right, start, acc, f);
+            }
+            if let Some(res) = max_right(raw_node.left, start, acc, f) {
+                return Some(res);
+            }
+            let mid = M::op(acc, &raw_node.key.1);
+            if !f(&mid) {
+                return node;
+            }
+            *acc = mid;
+            first_fail(raw_node.right, acc, f)
+        }
+
+        debug_assert!(f(&M::identity()));
+        let node = max_right(self.root, start, &mut M::identity(), &f)?;
+        Some(&unsafe { node.as_ref() }.key.0)
+    }
+
+    /// end未満のキーを降順に見ていき，fold((Excluded(k), Excluded(end)))に対して
+    /// fがtrueとなる最小のkを返す
+    /// すべてのキーについてtrueとなる場合はNone
+    /// fの条件はmax_rightと同じ
+    /// NOTE: O(log N)
+    pub fn min_left<Q>(&self, end: &Q, f: impl Fn(&M::S) -> bool) -> Option<&K>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        fn min_left<K, M, Q>(
+            node: MonoidLink<K, M>,
+            end: &Q,
+            acc: &mut M::S,
+            f: &impl Fn(&M::S) -> bool,
+        ) -> MonoidLink<K, M>
+        where
+            K: Borrow<Q>,
+            M: Monoid,
+            Q: Ord + ?Sized,
+        {
+            let raw_node = unsafe { node?.as_ref() };
+            if raw_node.key.0.borrow() >= end {
+                return min_left(raw_node.left, end, acc, f);
+            }
+            if let Some(res) = min_left(raw_node.right, end, acc, f) {
+                return Some(res);
+            }
+            let mid = M::op(&raw_node.key.1, acc);
+            if !f(&mid) {
+                return node;
+            }
+            *acc = mid;
+            last_fail(raw_node.left, acc, f)
+        }
+
+        debug_assert!(f(&M::identity()));
+        let node = min_left(self.root, end, &mut M::identity(), &f)?;
+        Some(&unsafe { node.as_ref() }.key.0)
+    }
+
+    /// キーが範囲内にある要素を昇順に返すイテレータ
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, M>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Range(NodeRange::new(
+            self.root,
+            |x| is_after_start(x.0.borrow(), range.start_bound()),
+            |x| is_before_end(x.0.borrow(), range.end_bound()),
+        ))
+    }
+
+    pub fn iter(&self) -> Range<'_, K, M> {
+        Range(NodeRange::new(self.root, |_| true, |_| true))
+    }
+}
+
+impl<K, M: Monoid> Drop for AvlTreeMonoidMap<K, M> {
+    fn drop(&mut self) {
+        traverse_postorder(self.root, free);
+    }
+}
+
+impl<K: Clone, M: Monoid> Clone for AvlTreeMonoidMap<K, M> {
+    fn clone(&self) -> Self {
+        Self {
+            root: clone_tree(self.root, &mut BoxPool),
+            phantom: PhantomData,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        clone_tree_from(&mut self.root, source.root, &mut BoxPool);
+    }
+}
+
+impl<K, M: Monoid> Default for AvlTreeMonoidMap<K, M> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<K: Debug, M: Monoid> Debug for AvlTreeMonoidMap<K, M>
+where
+    M::S: Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K, M: Monoid> IntoIterator for &'a AvlTreeMonoidMap<K, M> {
+    type IntoIter = Range<'a, K, M>;
+    type Item = (&'a K, &'a M::S);
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord, M: Monoid> Extend<(K, M::S)> for AvlTreeMonoidMap<K, M> {
+    fn extend<I: IntoIterator<Item = (K, M::S)>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|(key, value)| {
+            self.insert(key, value);
+        });
+    }
+}
+
+impl<K: Ord, M: Monoid> FromIterator<(K, M::S)> for AvlTreeMonoidMap<K, M> {
+    fn from_iter<I: IntoIterator<Item = (K, M::S)>>(iter: I) -> Self {
+        let mut res = Self::new();
+        res.extend(iter);
+        res
+    }
+}
+
+/// キーが範囲内にある要素を昇順に返すイテレータ
+pub struct Range<'a, K, M: Monoid>(NodeRange<'a, (K, M::S), Fold<M>>);
+
+impl<'a, K: 'a, M: Monoid> Iterator for Range<'a, K, M> {
+    type Item = (&'a K, &'a M::S);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = unsafe { self.0.next()?.as_ref() };
+        Some((&node.key.0, &node.key.1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<'a, K: 'a, M: Monoid> DoubleEndedIterator for Range<'a, K, M> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = unsafe { self.0.next_back()?.as_ref() };
+        Some((&node.key.0, &node.key.1))
+    }
+}
+
+impl<'a, K: 'a, M: Monoid> ExactSizeIterator for Range<'a, K, M> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{AvlTreeMonoidMap, Monoid};
+
+    struct Sum;
+
+    impl Monoid for Sum {
+        type S = i64;
+        fn identity() -> Self::S {
+            0
+        }
+        fn op(a: &Self::S, b: &Self::S) -> Self::S {
+            a + b
+        }
+    }
+
+    /// 非可換なモノイド
+    struct Concat;
+
+    impl Monoid for Concat {
+        type S = String;
+        fn identity() -> Self::S {
+            String::new()
+        }
+        fn op(a: &Self::S, b: &Self::S) -> Self::S {
+            a.clone() + b
+        }
+    }
+
+    #[test]
+    fn test_avl_tree_monoid_map_fold() {
+        let mut map = AvlTreeMonoidMap::<_, Sum>::from_iter([(10, 1), (20, 2), (30, 4), (40, 8)]);
+        assert_eq!(map.fold(..), 15);
+        assert_eq!(map.fold(20..40), 6);
+        assert_eq!(map.fold(15..=40), 14);
+        assert_eq!(map.fold(41..), 0);
+        assert_eq!(map.insert(20, 16), Some(2));
+        assert_eq!(map.fold(..30), 17);
+        assert_eq!(map.remove(&10), Some(1));
+        assert_eq!(map.remove(&10), None);
+        assert_eq!(map.fold(..), 28);
+        assert!(map.iter().eq([(&20, &16), (&30, &4), (&40, &8)]));
+
+        let map = AvlTreeMonoidMap::<_, Concat>::from_iter(
+            ["c", "a", "d", "b"].map(|s| (s, s.to_string())),
+        );
+        assert_eq!(map.fold("b".."d"), "bc");
+        assert_eq!(map.fold::<&str, _>(..), "abcd");
+    }
+
+    #[test]
+    fn test_avl_tree_monoid_map_max_right_min_left() {
+        let map = AvlTreeMonoidMap::<_, Sum>::from_iter([(1, 3), (2, 1), (4, 4), (7, 1), (9, 5)]);
+        assert_eq!(map.max_right(&2, |&s| s <= 5), Some(&7));
+        assert_eq!(map.max_right(&2, |&s| s <= 0), Some(&2));
+        assert_eq!(map.max_right(&0, |&s| s <= 100), None);
+        assert_eq!(map.max_right(&3, |&s| s <= 5), Some(&9));
+        assert_eq!(map.min_left(&9, |&s| s <= 5), Some(&2));
+        assert_eq!(map.min_left(&9, |&s| s <= 0), Some(&7));
+        assert_eq!(map.min_left(&10, |&s| s <= 100), None);
+        assert_eq!(map.min_left(&4, |&s| s <= 3), Some(&1));
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_monoid_map_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeMap;
+
+        let mut rng = thread_rng();
+
+        for _ in 0..5 {
+            let mut map = AvlTreeMonoidMap::<i32, Sum>::new();
+            let mut b = BTreeMap::new();
+            for _ in 0..1000 {
+                // 0: insert
+                // 1: remove
+                // 2: fold
+                // 3: max_right
+                // 4: min_left
+                let t = rng.gen_range(0..5);
+                let x = rng.gen_range(-50..=50);
+                match t {
+                    0 => {
+                        let v = rng.gen_range(0..100);
+                        assert_eq!(map.insert(x, v), b.insert(x, v));
+                    }
+                    1 => assert_eq!(map.remove(&x), b.remove(&x)),
+                    2 => {
+                        let y = rng.gen_range(x..=50);
+                        assert_eq!(map.fold(x..y), b.range(x..y).map(|(_, v)| v).sum());
+                    }
+                    3 => {
+                        let limit = rng.gen_range(0..500);
+                        let mut acc = 0;
+                        let expected = b
+                            .range(x..)
+                            .find(|&(_, v)| {
+                                acc += v;
+                                acc > limit
+                            })
+                            .map(|(k, _)| k);
+                        assert_eq!(map.max_right(&x, |&s| s <= limit), expected);
+                    }
+                    4 => {
+                        let limit = rng.gen_range(0..500);
+                        let mut acc = 0;
+                        let expected = b
+                            .range(..x)
+                            .rev()
+                            .find(|&(_, v)| {
+                                acc += v;
+                                acc > limit
+                            })
+                            .map(|(k, _)| k);
+                        assert_eq!(map.min_left(&x, |&s| s <= limit), expected);
+                    }
+                    _ => {}
+                }
+                assert_eq!(map.len(), b.len());
+            }
+            assert!(map.clone().iter().eq(b.iter()));
+        }
+    }
+}
@@ -0,0 +1,385 @@
+//! AVL木によるordered multisetの実装
+//! 重複する要素はそれぞれ別のノードとして保持する
+//! 等しい要素は挿入順に並ぶ
+
+use std::{borrow::Borrow, cmp::Ordering, fmt::Debug, marker::PhantomData, ops::RangeBounds};
+
+use crate::{
+    balance, clone_tree, clone_tree_from, count_by, free, is_after_start, is_before_end, merge,
+    node_len, remove_nth, split_by, traverse_postorder, BoxPool, IntoIter, Link, Node, NodePtr,
+    NodeRange, RangeIter,
+};
+
+/// rootに新しいノードを挿入する
+/// 等しい要素が存在する場合はそれらより後ろに挿入する
+fn insert_node<T: Ord>(root: &mut Link<T>, mut new_node: NodePtr<T>) {
+    if let Some(node) = root.map(|mut node| unsafe { node.as_mut() }) {
+        match unsafe { new_node.as_ref() }.key.cmp(&node.key) {
+            Ordering::Less => insert_node(&mut node.left, new_node),
+            Ordering::Equal | Ordering::Greater => insert_node(&mut node.right, new_node),
+        }
+        balance(root);
+    } else {
+        unsafe {
+            new_node.as_mut().left = None;
+            new_node.as_mut().right = None;
+        }
+        *root = Some(new_node);
+    }
+}
+
+/// keyより小さい要素，keyと等しい要素，keyより大きい要素からなる3つの木に分割する
+fn split3<T, Q>(root: Link<T>, key: &Q) -> (Link<T>, Link<T>, Link<T>)
+where
+    T: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    let (less, rest) = split_by(root, &mut |x: &T| x.borrow() < key);
+    let (equal, greater) = split_by(rest, &mut |x: &T| x.borrow() <= key);
+    (less, equal, greater)
+}
+
+/// AVL木によるordered multisetの実装
+pub struct AvlTreeMultiSet<T> {
+    root: Link<T>,
+    phantom: PhantomData<Box<Node<T>>>,
+}
+
+unsafe impl<T: Send> Send for AvlTreeMultiSet<T> {}
+unsafe impl<T: Sync> Sync for AvlTreeMultiSet<T> {}
+
+impl<T> AvlTreeMultiSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// 重複を含めた要素数
+    pub fn len(&self) -> usize {
+        node_len(self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn insert(&mut self, key: T)
+    where
+        T: Ord,
+    {
+        insert_node(&mut self.root, Node::new(key));
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut cur = self.root;
+        while let Some(node) = cur.map(|node| unsafe { node.as_ref() }) {
+            match key.cmp(node.key.borrow()) {
+                Ordering::Equal => return true,
+                Ordering::Less => cur = node.left,
+                Ordering::Greater => cur = node.right,
+            }
+        }
+        false
+    }
+
+    /// keyと等しい要素の数
+    /// NOTE: O(log N)
+    pub fn count<Q>(&self, key: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        count_by(self.root, |x| x.borrow() <= key) - count_by(self.root, |x| x.borrow() < key)
+    }
+
+    /// keyと等しい要素のうち最初に挿入されたものを1つ削除する
+    /// NOTE: O(log N)
+    pub fn remove_one<Q>(&mut self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        // 等しい要素は挿入順に並ぶので，その中で最も左のノードを削除する
+        let index = count_by(self.root, |x| x.borrow() < key);
+        match self.get_nth(index) {
+            Some(x) if x.borrow() == key => {
+                free(remove_nth(&mut self.root, index));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// keyと等しい要素をすべて削除して，削除した要素数を返す
+    /// NOTE: 削除する要素数Kに対してO(log N + K)
+    pub fn remove_all<Q>(&mut self, key: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (less, equal, greater) = split3(self.root.take(), key);
+        let res = node_len(equal);
+        traverse_postorder(equal, free);
+        self.root = merge(less, greater);
+        res
+    }
+
+    /// 重複を含めて昇順n番目の要素
+    pub fn get_nth(&self, mut n: usize) -> Option<&T> {
+        let mut cur = self.root;
+        while let Some(node) = cur.map(|node| unsafe { node.as_ref() }) {
+            let left_len = node_len(node.left);
+            if n == left_len {
+                return Some(&node.key);
+            } else if n < left_len {
+                cur = node.left;
+            } else {
+                cur = node.right;
+                n -= left_len + 1;
+            }
+        }
+        None
+    }
+
+    /// 重複を含めて降順n番目の要素
+    pub fn get_nth_back(&self, n: usize) -> Option<&T> {
+        self.get_nth(self.len().checked_sub(n + 1)?)
+    }
+
+    /// 重複を含めて範囲内の要素を昇順に返すイテレータ
+    pub fn range<Q, R>(&self, range: R) -> RangeIter<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        RangeIter(NodeRange::new(
+            self.root,
+            |x| is_after_start(x.borrow(), range.start_bound()),
+            |x| is_before_end(x.borrow(), range.end_bound()),
+        ))
+    }
+
+    pub fn iter(&self) -> RangeIter<'_, T> {
+        RangeIter(NodeRange::new(self.root, |_| true, |_| true))
+    }
+}
+
+impl<T> Drop for AvlTreeMultiSet<T> {
+    fn drop(&mut self) {
+        traverse_postorder(self.root, free);
+    }
+}
+
+impl<T: Clone> Clone for AvlTreeMultiSet<T> {
+    fn clone(&self) -> Self {
+        Self {
+            root: clone_tree(self.root, &mut BoxPool),
+            phantom: PhantomData,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        clone_tree_from(&mut self.root, source.root, &mut BoxPool);
+    }
+}
+
+impl<T> Default for AvlTreeMultiSet<T> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for AvlTreeMultiSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for AvlTreeMultiSet<T> {}
+
+impl<T: Debug> Debug for AvlTreeMultiSet<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a AvlTreeMultiSet<T> {
+    type IntoIter = RangeIter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for AvlTreeMultiSet<T> {
+    type IntoIter = IntoIter<T>;
+    type Item = T;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        IntoIter::new(self.root.take(), BoxPool)
+    }
+}
+
+impl<T: Ord> Extend<T> for AvlTreeMultiSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|x| self.insert(x));
+    }
+}
+
+impl<T: Ord> FromIterator<T> for AvlTreeMultiSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut res = Self::new();
+        res.extend(iter);
+        res
+    }
+}
+
+impl<T: Ord> From<Vec<T>> for AvlTreeMultiSet<T> {
+    fn from(v: Vec<T>) -> Self {
+        Self::from_iter(v)
+    }
+}
+
+impl<T: Ord, const N: usize> From<[T; N]> for AvlTreeMultiSet<T> {
+    fn from(v: [T; N]) -> Self {
+        Self::from_iter(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AvlTreeMultiSet;
+    use crate::tests::Item;
+
+    #[test]
+    fn test_avl_tree_multiset_insert_and_count() {
+        let mut st = AvlTreeMultiSet::new();
+        st.insert(3);
+        st.insert(1);
+        st.insert(3);
+        st.insert(2);
+        st.insert(3);
+        assert_eq!(st.len(), 5);
+        assert_eq!(st.count(&3), 3);
+        assert_eq!(st.count(&1), 1);
+        assert_eq!(st.count(&4), 0);
+        assert!(st.contains(&2));
+        assert!(st.iter().copied().eq([1, 2, 3, 3, 3]));
+        assert!(st.iter().rev().copied().eq([3, 3, 3, 2, 1]));
+    }
+
+    #[test]
+    fn test_avl_tree_multiset_remove() {
+        let mut st = AvlTreeMultiSet::from([5, 1, 5, 3, 5, 1]);
+        assert!(st.remove_one(&5));
+        assert_eq!(st.count(&5), 2);
+        assert!(!st.remove_one(&4));
+        assert_eq!(st.remove_all(&5), 2);
+        assert_eq!(st.remove_all(&5), 0);
+        assert!(st.iter().copied().eq([1, 1, 3]));
+        assert!(st.remove_one(&1));
+        assert!(st.remove_one(&1));
+        assert!(!st.remove_one(&1));
+        assert!(st.iter().copied().eq([3]));
+    }
+
+    #[test]
+    fn test_avl_tree_multiset_remove_one_first_inserted() {
+        // 挿入順の番号を2つ目の値に持たせる
+        let mut st = AvlTreeMultiSet::new();
+        for (i, &x) in [3, 1, 3, 2, 3, 3, 1, 3].iter().enumerate() {
+            st.insert(Item(x, i));
+        }
+        assert!(st.remove_one(&Item(3, 0)));
+        assert!(st.iter().map(|x| x.1).eq([1, 6, 3, 2, 4, 5, 7]));
+        assert!(st.remove_one(&Item(3, 0)));
+        assert!(st.remove_one(&Item(1, 0)));
+        assert!(st.iter().map(|x| x.1).eq([6, 3, 4, 5, 7]));
+    }
+
+    #[test]
+    fn test_avl_tree_multiset_get_nth_and_range() {
+        let st = AvlTreeMultiSet::from([2, 4, 4, 4, 6, 8, 8]);
+        assert_eq!(st.get_nth(0), Some(&2));
+        assert_eq!(st.get_nth(1), Some(&4));
+        assert_eq!(st.get_nth(3), Some(&4));
+        assert_eq!(st.get_nth(4), Some(&6));
+        assert_eq!(st.get_nth(6), Some(&8));
+        assert_eq!(st.get_nth(7), None);
+        assert_eq!(st.get_nth_back(0), Some(&8));
+        assert_eq!(st.get_nth_back(2), Some(&6));
+        assert!(st.range(4..8).copied().eq([4, 4, 4, 6]));
+        assert!(st.range(4..=8).rev().copied().eq([8, 8, 6, 4, 4, 4]));
+        assert_eq!(st.range(3..7).len(), 4);
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_multiset_random() {
+        use rand::{thread_rng, Rng};
+
+        let mut rng = thread_rng();
+
+        for _ in 0..5 {
+            let mut avl = AvlTreeMultiSet::new();
+            let mut v: Vec<i32> = vec![];
+            for _ in 0..1000 {
+                // 0: insert
+                // 1: count
+                // 2: remove_one
+                // 3: remove_all
+                // 4: nth
+                // 5: range
+                let t = rng.gen_range(0..6);
+                let x = rng.gen_range(-30..=30);
+                match t {
+                    0 => {
+                        avl.insert(x);
+                        let i = v.partition_point(|&y| y <= x);
+                        v.insert(i, x);
+                    }
+                    1 => {
+                        assert_eq!(avl.count(&x), v.iter().filter(|&&y| y == x).count());
+                    }
+                    2 => {
+                        let i = v.iter().position(|&y| y == x);
+                        assert_eq!(avl.remove_one(&x), i.is_some());
+                        if let Some(i) = i {
+                            v.remove(i);
+                        }
+                    }
+                    3 => {
+                        let count = v.iter().filter(|&&y| y == x).count();
+                        assert_eq!(avl.remove_all(&x), count);
+                        v.retain(|&y| y != x);
+                    }
+                    4 => {
+                        let k = rng.gen_range(0..100);
+                        assert_eq!(avl.get_nth(k), v.get(k));
+                    }
+                    5 => {
+                        let y = rng.gen_range(x..=30);
+                        assert!(avl.range(x..y).eq(v.iter().filter(|&&z| x <= z && z < y)));
+                    }
+                    _ => {}
+                }
+                assert_eq!(avl.len(), v.len());
+                assert!(avl.iter().eq(v.iter()));
+            }
+            assert!(avl.into_iter().eq(v.into_iter()));
+        }
+    }
+}
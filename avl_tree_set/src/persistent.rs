@@ -0,0 +1,530 @@
+//! 永続AVL木によるordered setの実装
+//! 更新時は根から変更箇所までの経路上のノードだけを作り直し，それ以外の部分木は`Arc`で共有する
+//! 要素も`Arc`で共有するので，作り直すノードのために要素を複製しない
+
+use std::{borrow::Borrow, cmp::Ordering, fmt::Debug, sync::Arc};
+
+struct Node<T> {
+    key: Arc<T>,
+    len: usize,
+    height: i32,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+type Link<T> = Option<Arc<Node<T>>>;
+
+#[inline]
+fn node_len<T>(node: &Link<T>) -> usize {
+    node.as_ref().map_or(0, |node| node.len)
+}
+
+#[inline]
+fn node_height<T>(node: &Link<T>) -> i32 {
+    node.as_ref().map_or(0, |node| node.height)
+}
+
+/// 左右の部分木の高さの差が1以下であるような木を作る
+fn new_node<T>(key: Arc<T>, left: Link<T>, right: Link<T>) -> Arc<Node<T>> {
+    Arc::new(Node {
+        len: node_len(&left) + node_len(&right) + 1,
+        height: node_height(&left).max(node_height(&right)) + 1,
+        key,
+        left,
+        right,
+    })
+}
+
+/// 左右の部分木の高さの差が2以下であるような木を，回転して平衡させながら作る
+fn balance<T>(key: Arc<T>, left: Link<T>, right: Link<T>) -> Arc<Node<T>> {
+    let d = node_height(&left) - node_height(&right);
+    if d > 1 {
+        // 左部分木が高い場合
+        let l = left.unwrap();
+        if node_height(&l.left) >= node_height(&l.right) {
+            let right = new_node(key, l.right.clone(), right);
+            new_node(l.key.clone(), l.left.clone(), Some(right))
+        } else {
+            let lr = l.right.as_ref().unwrap();
+            let left = new_node(l.key.clone(), l.left.clone(), lr.left.clone());
+            let right = new_node(key, lr.right.clone(), right);
+            new_node(lr.key.clone(), Some(left), Some(right))
+        }
+    } else if d < -1 {
+        // 右部分木が高い場合
+        let r = right.unwrap();
+        if node_height(&r.right) >= node_height(&r.left) {
+            let left = new_node(key, left, r.left.clone());
+            new_node(r.key.clone(), Some(left), r.right.clone())
+        } else {
+            let rl = r.left.as_ref().unwrap();
+            let left = new_node(key, left, rl.left.clone());
+            let right = new_node(r.key.clone(), rl.right.clone(), r.right.clone());
+            new_node(rl.key.clone(), Some(left), Some(right))
+        }
+    } else {
+        new_node(key, left, right)
+    }
+}
+
+/// leftの全要素 < key < rightの全要素 であるような2つの木をkeyを介して連結する
+/// 計算量は2つの木の高さの差に対してO(|h(left) - h(right)| + 1)
+fn join_with_root<T>(left: Link<T>, key: Arc<T>, right: Link<T>) -> Arc<Node<T>> {
+    let d = node_height(&left) - node_height(&right);
+    if d > 1 {
+        let l = left.unwrap();
+        let r = join_with_root(l.right.clone(), key, right);
+        balance(l.key.clone(), l.left.clone(), Some(r))
+    } else if d < -1 {
+        let r = right.unwrap();
+        let l = join_with_root(left, key, r.left.clone());
+        balance(r.key.clone(), Some(l), r.right.clone())
+    } else {
+        new_node(key, left, right)
+    }
+}
+
+/// 最大の要素を取り除いた木と最大の要素を返す
+fn split_last<T>(node: &Node<T>) -> (Link<T>, Arc<T>) {
+    match &node.right {
+        Some(right) => {
+            let (right, last) = split_last(right);
+            (
+                Some(balance(node.key.clone(), node.left.clone(), right)),
+                last,
+            )
+        }
+        None => (node.left.clone(), node.key.clone()),
+    }
+}
+
+/// leftの全要素 < rightの全要素 であるような2つの木を連結する
+fn join<T>(left: Link<T>, right: Link<T>) -> Link<T> {
+    match left {
+        Some(left) => {
+            let (left, last) = split_last(&left);
+            Some(join_with_root(left, last, right))
+        }
+        None => right,
+    }
+}
+
+/// keyより小さい要素からなる木とkey以上の要素からなる木に分割する
+fn split<T, Q>(node: &Link<T>, key: &Q) -> (Link<T>, Link<T>)
+where
+    T: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    let Some(node) = node else {
+        return (None, None);
+    };
+    if (*node.key).borrow() < key {
+        let (l, r) = split(&node.right, key);
+        let l = join_with_root(node.left.clone(), node.key.clone(), l);
+        (Some(l), r)
+    } else {
+        let (l, r) = split(&node.left, key);
+        let r = join_with_root(r, node.key.clone(), node.right.clone());
+        (l, Some(r))
+    }
+}
+
+/// 永続AVL木によるordered set
+/// 更新操作は自身を変更せず，新しい集合を返す
+/// 複製はO(1)で，更新前後の集合は変更されなかった部分木を共有する
+pub struct PersistentAvlTreeSet<T> {
+    root: Link<T>,
+}
+
+impl<T> PersistentAvlTreeSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        node_len(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 2つの集合が同じ木を共有しているか
+    /// NOTE: O(1)
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.root, &other.root) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut cur = &self.root;
+        while let Some(node) = cur {
+            match key.cmp((*node.key).borrow()) {
+                Ordering::Equal => return true,
+                Ordering::Less => cur = &node.left,
+                Ordering::Greater => cur = &node.right,
+            }
+        }
+        false
+    }
+
+    /// 昇順n番目の要素
+    pub fn get_nth(&self, mut n: usize) -> Option<&T> {
+        let mut cur = &self.root;
+        while let Some(node) = cur {
+            let left_len = node_len(&node.left);
+            if n == left_len {
+                return Some(&node.key);
+            } else if n < left_len {
+                cur = &node.left;
+            } else {
+                cur = &node.right;
+                n -= left_len + 1;
+            }
+        }
+        None
+    }
+
+    /// 最小の要素
+    pub fn first(&self) -> Option<&T> {
+        self.get_nth(0)
+    }
+
+    /// 最大の要素
+    pub fn last(&self) -> Option<&T> {
+        self.get_nth(self.len().checked_sub(1)?)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter {
+            stack_left: vec![],
+            stack_right: vec![],
+            len: self.len(),
+        };
+        iter.push_left(&self.root);
+        iter.push_right(&self.root);
+        iter
+    }
+}
+
+impl<T: Ord> PersistentAvlTreeSet<T> {
+    /// keyを挿入した集合を返す
+    /// NOTE: O(log N)
+    pub fn insert(&self, key: T) -> Self {
+        fn insert<T: Ord>(node: &Link<T>, key: T) -> Option<Arc<Node<T>>> {
+            let Some(node) = node else {
+                return Some(new_node(Arc::new(key), None, None));
+            };
+            match key.cmp(&node.key) {
+                Ordering::Equal => None,
+                Ordering::Less => {
+                    let left = insert(&node.left, key)?;
+                    Some(balance(node.key.clone(), Some(left), node.right.clone()))
+                }
+                Ordering::Greater => {
+                    let right = insert(&node.right, key)?;
+                    Some(balance(node.key.clone(), node.left.clone(), Some(right)))
+                }
+            }
+        }
+
+        match insert(&self.root, key) {
+            Some(root) => Self { root: Some(root) },
+            None => self.clone(),
+        }
+    }
+
+    /// keyと等しい要素を削除した集合を返す
+    /// NOTE: O(log N)
+    pub fn remove<Q>(&self, key: &Q) -> Self
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        fn remove<T, Q>(node: &Link<T>, key: &Q) -> Option<Link<T>>
+        where
+            T: Borrow<Q>,
+            Q: Ord + ?Sized,
+        {
+            let node = node.as_ref()?;
+            match key.cmp((*node.key).borrow()) {
+                Ordering::Equal => Some(join(node.left.clone(), node.right.clone())),
+                Ordering::Less => {
+                    let left = remove(&node.left, key)?;
+                    Some(Some(balance(node.key.clone(), left, node.right.clone())))
+                }
+                Ordering::Greater => {
+                    let right = remove(&node.right, key)?;
+                    Some(Some(balance(node.key.clone(), node.left.clone(), right)))
+                }
+            }
+        }
+
+        match remove(&self.root, key) {
+            Some(root) => Self { root },
+            None => self.clone(),
+        }
+    }
+
+    /// keyより小さい要素からなる集合とkey以上の要素からなる集合に分割する
+    /// NOTE: O(log N)
+    pub fn split<Q>(&self, key: &Q) -> (Self, Self)
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (left, right) = split(&self.root, key);
+        (Self { root: left }, Self { root: right })
+    }
+
+    /// selfの全要素 < otherの全要素 であるような2つの集合を連結した集合を返す
+    /// NOTE: O(log N)
+    ///
+    /// # Panics
+    /// selfの最大の要素がotherの最小の要素以上の場合
+    pub fn join(&self, other: &Self) -> Self {
+        if let (Some(last), Some(first)) = (self.last(), other.first()) {
+            assert!(last < first, "the sets are not ordered");
+        }
+        Self {
+            root: join(self.root.clone(), other.root.clone()),
+        }
+    }
+}
+
+impl<T> Clone for PersistentAvlTreeSet<T> {
+    /// 木を共有するのでO(1)
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+        }
+    }
+}
+
+impl<T> Default for PersistentAvlTreeSet<T> {
+    fn default() -> Self {
+        Self { root: None }
+    }
+}
+
+impl<T: PartialEq> PartialEq for PersistentAvlTreeSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other) || (self.len() == other.len() && self.iter().eq(other.iter()))
+    }
+}
+
+impl<T: Eq> Eq for PersistentAvlTreeSet<T> {}
+
+impl<T: Debug> Debug for PersistentAvlTreeSet<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a PersistentAvlTreeSet<T> {
+    type IntoIter = Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for PersistentAvlTreeSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::new(), |set, key| set.insert(key))
+    }
+}
+
+/// 要素を昇順に返すイテレータ
+pub struct Iter<'a, T> {
+    stack_left: Vec<&'a Node<T>>,
+    stack_right: Vec<&'a Node<T>>,
+    /// まだ返していない要素数
+    len: usize,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_left(&mut self, mut node: &'a Link<T>) {
+        while let Some(n) = node {
+            self.stack_left.push(n);
+            node = &n.left;
+        }
+    }
+
+    fn push_right(&mut self, mut node: &'a Link<T>) {
+        while let Some(n) = node {
+            self.stack_right.push(n);
+            node = &n.right;
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let node = self.stack_left.pop()?;
+        self.push_left(&node.right);
+        Some(&node.key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let node = self.stack_right.pop()?;
+        self.push_right(&node.left);
+        Some(&node.key)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{node_height, node_len, Link, PersistentAvlTreeSet};
+
+    fn is_balanced<T>(node: &Link<T>) -> bool {
+        node.as_ref().is_none_or(|node| {
+            is_balanced(&node.left)
+                && is_balanced(&node.right)
+                && (node_height(&node.left) - node_height(&node.right)).abs() <= 1
+                && node.len == node_len(&node.left) + node_len(&node.right) + 1
+        })
+    }
+
+    #[test]
+    fn test_persistent_avl_tree_set_snapshot() {
+        let s0 = PersistentAvlTreeSet::from_iter([3, 1, 4, 5, 9, 2, 6]);
+        let s1 = s0.insert(7);
+        let s2 = s1.remove(&3);
+        let s3 = s2.insert(7);
+        assert!(s0.iter().copied().eq([1, 2, 3, 4, 5, 6, 9]));
+        assert!(s1.iter().copied().eq([1, 2, 3, 4, 5, 6, 7, 9]));
+        assert!(s2.iter().copied().eq([1, 2, 4, 5, 6, 7, 9]));
+        assert!(s3.ptr_eq(&s2));
+        assert!(s0.remove(&8).ptr_eq(&s0));
+        assert!(s0.clone().ptr_eq(&s0));
+        assert!(s1.contains(&7) && !s0.contains(&7));
+        assert_eq!(s2.get_nth(2), Some(&4));
+
+        let (l, r) = s1.split(&5);
+        assert!(l.iter().copied().eq([1, 2, 3, 4]));
+        assert!(r.iter().copied().eq([5, 6, 7, 9]));
+        assert_eq!(l.join(&r), s1);
+        assert!(is_balanced(&l.root) && is_balanced(&r.root));
+    }
+
+    #[test]
+    fn test_persistent_avl_tree_set_iter_double_ended() {
+        let st = PersistentAvlTreeSet::from_iter(0..10);
+        assert!(st.iter().rev().copied().eq((0..10).rev()));
+
+        let mut iter = st.iter();
+        assert_eq!(iter.next(), Some(&0));
+        assert_eq!(iter.next_back(), Some(&9));
+        assert_eq!(iter.next_back(), Some(&8));
+        assert_eq!(iter.len(), 7);
+        assert!(iter.by_ref().take(6).copied().eq(1..7));
+        assert_eq!(iter.next_back(), Some(&7));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn test_persistent_avl_tree_set_snapshot_threads() {
+        // 要素がCloneでなくてもよい
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Key(i32);
+
+        let mut st = PersistentAvlTreeSet::from_iter((0..100).map(Key));
+        let handles = (0..4)
+            .map(|i| {
+                let snapshot = st.clone();
+                st = st.insert(Key(100 + i));
+                std::thread::spawn(move || snapshot.iter().map(|x| x.0).sum::<i32>())
+            })
+            .collect::<Vec<_>>();
+        for (i, handle) in handles.into_iter().enumerate() {
+            let expected = (0..100).sum::<i32>() + (100..100 + i as i32).sum::<i32>();
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+        assert_eq!(st.len(), 104);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_persistent_avl_tree_set_join_unordered() {
+        let a = PersistentAvlTreeSet::from_iter([1, 5]);
+        let b = PersistentAvlTreeSet::from_iter([3, 7]);
+        a.join(&b);
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_persistent_avl_tree_set_random() {
+        use rand::{thread_rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = thread_rng();
+
+        // すべての版を保持して，過去の版が変化しないことを確かめる
+        let mut versions = vec![(PersistentAvlTreeSet::new(), BTreeSet::new())];
+        for _ in 0..1000 {
+            // 0: insert
+            // 1: remove
+            // 2: split and join
+            let t = rng.gen_range(0..3);
+            let x = rng.gen_range(-100..=100);
+            let (avl, b) = &versions[rng.gen_range(0..versions.len())];
+            let (mut avl, mut b) = (avl.clone(), b.clone());
+            match t {
+                0 => {
+                    avl = avl.insert(x);
+                    b.insert(x);
+                }
+                1 => {
+                    avl = avl.remove(&x);
+                    b.remove(&x);
+                }
+                2 => {
+                    let (l, r) = avl.split(&x);
+                    let br = b.split_off(&x);
+                    assert!(l.iter().eq(b.iter()));
+                    assert!(r.iter().eq(br.iter()));
+                    assert!(is_balanced(&l.root) && is_balanced(&r.root));
+                    avl = l.join(&r);
+                    b.extend(br);
+                }
+                _ => {}
+            }
+            assert!(is_balanced(&avl.root));
+            versions.push((avl, b));
+        }
+        for (avl, b) in versions {
+            assert_eq!(avl.len(), b.len());
+            assert!(avl.iter().eq(b.iter()));
+        }
+    }
+}
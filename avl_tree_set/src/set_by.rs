@@ -0,0 +1,393 @@
+//! 比較関数を実行時に与えるAVL木によるordered setの実装
+//! 比較関数は全順序を与えるものに限る
+
+use std::{cmp::Ordering, fmt::Debug, marker::PhantomData, ops::RangeBounds};
+
+use crate::{
+    build_sorted, clone_tree, clone_tree_from, count_by, first_not_by, free, insert_node_by,
+    is_after_start_by, is_before_end_by, last_by, node_len, remove_node_by, remove_nth, split_by,
+    traverse_postorder, BoxPool, IntoIter, Link, Node, NodeRange, RangeIter,
+};
+
+/// 比較関数を実行時に与えるAVL木によるordered setの実装
+/// 降順や射影したキーによる順序など，Ordとは異なる順序で要素を管理する
+pub struct AvlTreeSetBy<T, C> {
+    root: Link<T>,
+    cmp: C,
+    phantom: PhantomData<Box<Node<T>>>,
+}
+
+unsafe impl<T: Send, C: Send> Send for AvlTreeSetBy<T, C> {}
+unsafe impl<T: Sync, C: Sync> Sync for AvlTreeSetBy<T, C> {}
+
+impl<T, C> AvlTreeSetBy<T, C>
+where
+    C: Fn(&T, &T) -> Ordering,
+{
+    /// 比較関数cmpによる順序で要素を管理する空の集合
+    pub fn new(cmp: C) -> Self {
+        Self {
+            root: None,
+            cmp,
+            phantom: PhantomData,
+        }
+    }
+
+    /// 比較関数cmpによる順序で要素を管理する集合をiterから構築する
+    /// 等しい要素は最初に現れたものを残す
+    /// NOTE: 比較関数による順序で並んでいればO(N)，そうでなければO(N log N)
+    pub fn from_iter_by<I: IntoIterator<Item = T>>(iter: I, cmp: C) -> Self {
+        let mut v = iter.into_iter().collect::<Vec<_>>();
+        if !v.is_sorted_by(|x, y| cmp(x, y) != Ordering::Greater) {
+            v.sort_by(&cmp);
+        }
+        v.dedup_by(|x, y| cmp(x, y) == Ordering::Equal);
+        let len = v.len();
+        let root = build_sorted(&mut v.into_iter().map(Node::new), len);
+        Self {
+            root,
+            cmp,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        traverse_postorder(self.root.take(), free);
+    }
+
+    pub fn len(&self) -> usize {
+        node_len(self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// keyと等しい要素のノード
+    fn find(&self, key: &T) -> Link<T> {
+        let node = first_not_by(self.root, |x| (self.cmp)(x, key) == Ordering::Less)?;
+        ((self.cmp)(&unsafe { node.as_ref() }.key, key) == Ordering::Equal).then_some(node)
+    }
+
+    /// 比較関数による順序でkeyより小さい要素の数
+    /// NOTE: O(log N)
+    pub fn rank(&self, key: &T) -> usize {
+        count_by(self.root, |x| (self.cmp)(x, key) == Ordering::Less)
+    }
+
+    pub fn insert(&mut self, key: T) -> bool {
+        insert_node_by(
+            &mut self.root,
+            Node::new(key),
+            &mut |x, y| (self.cmp)(x, y),
+            &mut BoxPool,
+        )
+    }
+
+    pub fn contains(&self, key: &T) -> bool {
+        self.find(key).is_some()
+    }
+
+    /// keyと等しい要素
+    pub fn get(&self, key: &T) -> Option<&T> {
+        self.find(key).map(|node| &unsafe { node.as_ref() }.key)
+    }
+
+    pub fn remove(&mut self, key: &T) -> bool {
+        self.take(key).is_some()
+    }
+
+    /// keyと等しい要素を削除して返す
+    /// NOTE: O(log N)
+    pub fn take(&mut self, key: &T) -> Option<T> {
+        let node = remove_node_by(&mut self.root, &mut |x| (self.cmp)(key, x))?;
+        let boxed = unsafe { Box::from_raw(node.as_ptr()) };
+        Some(boxed.key)
+    }
+
+    /// 昇順n番目の要素
+    pub fn get_nth(&self, mut n: usize) -> Option<&T> {
+        let mut cur = self.root;
+        while let Some(node) = cur.map(|node| unsafe { node.as_ref() }) {
+            let left_len = node_len(node.left);
+            if n == left_len {
+                return Some(&node.key);
+            } else if n < left_len {
+                cur = node.left;
+            } else {
+                cur = node.right;
+                n -= left_len + 1;
+            }
+        }
+        None
+    }
+
+    /// 降順n番目の要素
+    pub fn get_nth_back(&self, n: usize) -> Option<&T> {
+        self.get_nth(self.len().checked_sub(n + 1)?)
+    }
+
+    /// key以上最小の要素
+    pub fn lower_bound(&self, key: &T) -> Option<&T> {
+        let node = first_not_by(self.root, |x| (self.cmp)(x, key) == Ordering::Less)?;
+        Some(&unsafe { node.as_ref() }.key)
+    }
+
+    /// keyより大きい最小の要素
+    pub fn upper_bound(&self, key: &T) -> Option<&T> {
+        let node = first_not_by(self.root, |x| (self.cmp)(x, key) != Ordering::Greater)?;
+        Some(&unsafe { node.as_ref() }.key)
+    }
+
+    /// key以下最大の要素
+    pub fn floor(&self, key: &T) -> Option<&T> {
+        let node = last_by(self.root, |x| (self.cmp)(x, key) != Ordering::Greater)?;
+        Some(&unsafe { node.as_ref() }.key)
+    }
+
+    /// keyより小さい最大の要素
+    pub fn predecessor(&self, key: &T) -> Option<&T> {
+        let node = last_by(self.root, |x| (self.cmp)(x, key) == Ordering::Less)?;
+        Some(&unsafe { node.as_ref() }.key)
+    }
+
+    /// 最小の要素
+    pub fn first(&self) -> Option<&T> {
+        self.get_nth(0)
+    }
+
+    /// 最大の要素
+    pub fn last(&self) -> Option<&T> {
+        self.get_nth_back(0)
+    }
+
+    /// 最小の要素を削除して返す
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.pop_nth(0)
+    }
+
+    /// 最大の要素を削除して返す
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.pop_nth(self.len().checked_sub(1)?)
+    }
+
+    fn pop_nth(&mut self, n: usize) -> Option<T> {
+        (n < self.len()).then(|| {
+            let node = remove_nth(&mut self.root, n);
+            let boxed = unsafe { Box::from_raw(node.as_ptr()) };
+            boxed.key
+        })
+    }
+
+    /// 範囲内の要素を昇順に返すイテレータ
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> RangeIter<'_, T> {
+        RangeIter(NodeRange::new(
+            self.root,
+            |x| is_after_start_by(x, range.start_bound(), &self.cmp),
+            |x| is_before_end_by(x, range.end_bound(), &self.cmp),
+        ))
+    }
+
+    /// key以上の要素を分割して返す
+    /// NOTE: O(log N)
+    pub fn split_off(&mut self, key: &T) -> Self
+    where
+        C: Clone,
+    {
+        let (left, right) = split_by(self.root.take(), &mut |x: &T| {
+            (self.cmp)(x, key) == Ordering::Less
+        });
+        self.root = left;
+        Self {
+            root: right,
+            cmp: self.cmp.clone(),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn iter(&self) -> RangeIter<'_, T> {
+        RangeIter(NodeRange::new(self.root, |_| true, |_| true))
+    }
+}
+
+impl<T, C> Drop for AvlTreeSetBy<T, C> {
+    fn drop(&mut self) {
+        traverse_postorder(self.root, free);
+    }
+}
+
+impl<T: Clone, C: Clone> Clone for AvlTreeSetBy<T, C> {
+    fn clone(&self) -> Self {
+        Self {
+            root: clone_tree(self.root, &mut BoxPool),
+            cmp: self.cmp.clone(),
+            phantom: PhantomData,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        clone_tree_from(&mut self.root, source.root, &mut BoxPool);
+        self.cmp.clone_from(&source.cmp);
+    }
+}
+
+impl<T: Debug, C> Debug for AvlTreeSetBy<T, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set()
+            .entries(RangeIter(NodeRange::new(self.root, |_| true, |_| true)))
+            .finish()
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a AvlTreeSetBy<T, C>
+where
+    C: Fn(&T, &T) -> Ordering,
+{
+    type IntoIter = RangeIter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, C> IntoIterator for AvlTreeSetBy<T, C> {
+    type IntoIter = IntoIter<T>;
+    type Item = T;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        IntoIter::new(self.root.take(), BoxPool)
+    }
+}
+
+impl<T, C> Extend<T> for AvlTreeSetBy<T, C>
+where
+    C: Fn(&T, &T) -> Ordering,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|x| {
+            self.insert(x);
+        });
+    }
+}
+
+impl<T: Ord> FromIterator<T> for AvlTreeSetBy<T, fn(&T, &T) -> Ordering> {
+    /// Ordによる順序で要素を管理する
+    /// 他の順序で構築する場合はfrom_iter_byを使う
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::from_iter_by(iter, T::cmp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AvlTreeSetBy;
+    use std::ops::Bound;
+
+    #[test]
+    fn test_avl_tree_set_by_reverse() {
+        let mut st = AvlTreeSetBy::new(|a: &i32, b: &i32| b.cmp(a));
+        st.extend([3, 1, 4, 1, 5, 9, 2, 6]);
+        assert_eq!(st.len(), 7);
+        assert!(st.iter().copied().eq([9, 6, 5, 4, 3, 2, 1]));
+        assert!(st
+            .range((Bound::Included(6), Bound::Included(2)))
+            .copied()
+            .eq([6, 5, 4, 3, 2]));
+        assert_eq!(st.lower_bound(&7), Some(&6));
+        assert_eq!(st.upper_bound(&6), Some(&5));
+        assert_eq!(st.floor(&7), Some(&9));
+        assert_eq!(st.predecessor(&9), None);
+        assert_eq!(st.rank(&4), 3);
+        assert_eq!(st.pop_first(), Some(9));
+        assert_eq!(st.pop_last(), Some(1));
+        assert!(!st.insert(5));
+        assert!(st.remove(&5));
+        assert!(st.iter().copied().eq([6, 4, 3, 2]));
+    }
+
+    #[test]
+    fn test_avl_tree_set_by_key() {
+        let priority = [2, 0, 3, 1];
+        let mut st = AvlTreeSetBy::new(|a: &usize, b: &usize| priority[*a].cmp(&priority[*b]));
+        st.extend(0..4);
+        assert!(st.iter().copied().eq([1, 3, 0, 2]));
+
+        let mut st = AvlTreeSetBy::new(|a: &(i32, &str), b: &(i32, &str)| a.1.cmp(b.1));
+        st.extend([(1, "b"), (2, "a"), (3, "c")]);
+        assert_eq!(st.get(&(0, "b")), Some(&(1, "b")));
+        assert_eq!(st.take(&(0, "a")), Some((2, "a")));
+        assert!(st.iter().copied().eq([(1, "b"), (3, "c")]));
+        let rest = st.split_off(&(0, "c"));
+        assert!(st.iter().copied().eq([(1, "b")]));
+        assert!(rest.into_iter().eq([(3, "c")]));
+    }
+
+    #[test]
+    fn test_avl_tree_set_by_from_iter() {
+        let cmp = |a: &(i32, char), b: &(i32, char)| b.0.cmp(&a.0);
+        let mut st = AvlTreeSetBy::from_iter_by([(1, 'a'), (3, 'b'), (1, 'c'), (2, 'd')], cmp);
+        assert!(st.iter().copied().eq([(3, 'b'), (2, 'd'), (1, 'a')]));
+        assert_eq!(st.take(&(2, 'x')), Some((2, 'd')));
+        assert_eq!(st.take(&(2, 'x')), None);
+        st.extend([(5, 'e'), (3, 'f')]);
+        assert!(st.iter().copied().eq([(5, 'e'), (3, 'b'), (1, 'a')]));
+
+        let st = AvlTreeSetBy::from_iter_by(0..10, cmp_desc);
+        assert!(st.iter().copied().eq((0..10).rev()));
+        let st = AvlTreeSetBy::from_iter_by(Vec::<i32>::new(), cmp_desc);
+        assert!(st.is_empty());
+
+        let st: AvlTreeSetBy<_, _> = [3, 1, 4, 1, 5].into_iter().collect();
+        assert!(st.iter().copied().eq([1, 3, 4, 5]));
+    }
+
+    fn cmp_desc(a: &i32, b: &i32) -> std::cmp::Ordering {
+        b.cmp(a)
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_set_by_random() {
+        use rand::{thread_rng, Rng};
+        use std::{cmp::Reverse, collections::BTreeSet};
+
+        let mut rng = thread_rng();
+
+        for _ in 0..5 {
+            let mut avl = AvlTreeSetBy::new(|a: &i32, b: &i32| b.cmp(a));
+            let mut set = BTreeSet::new();
+            for _ in 0..1000 {
+                // 0: insert
+                // 1: remove
+                // 2: lower_bound
+                // 3: range
+                // 4: take
+                let t = rng.gen_range(0..5);
+                let x = rng.gen_range(-100..=100);
+                match t {
+                    0 => assert_eq!(avl.insert(x), set.insert(Reverse(x))),
+                    1 => assert_eq!(avl.remove(&x), set.remove(&Reverse(x))),
+                    2 => assert_eq!(
+                        avl.lower_bound(&x),
+                        set.range(Reverse(x)..).next().map(|Reverse(y)| y)
+                    ),
+                    3 => {
+                        let y = rng.gen_range(-100..=x);
+                        assert!(avl
+                            .range(x..y)
+                            .eq(set.range(Reverse(x)..Reverse(y)).map(|Reverse(z)| z)));
+                    }
+                    4 => assert_eq!(avl.take(&x), set.take(&Reverse(x)).map(|Reverse(y)| y)),
+                    _ => {}
+                }
+                assert_eq!(avl.len(), set.len());
+            }
+            assert!(avl
+                .clone()
+                .into_iter()
+                .eq(set.into_iter().map(|Reverse(z)| z)));
+        }
+    }
+}
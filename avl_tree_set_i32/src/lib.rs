@@ -4,19 +4,37 @@
 //! - iter_mut
 //! - range
 
-use std::{
-    cmp::Ordering,
-    fmt::Debug,
-    mem::{swap, take},
-    ptr::NonNull,
-};
-
-#[derive(Clone)]
-pub struct AVLTreeSet {
+pub use avl_tree_storage::{Arena, Boxed, Storage};
+
+use std::{cmp::Ordering, fmt::Debug, marker::PhantomData, ptr::NonNull};
+
+use avl_tree_storage::Pool;
+
+/// ノードごとにBoxで確保するAVLTreeSetIn
+pub type AVLTreeSet = AVLTreeSetIn<Boxed>;
+
+/// ノードをまとめて確保した領域に格納するAVLTreeSetIn
+pub type ArenaAVLTreeSet = AVLTreeSetIn<Arena>;
+
+/// ```compile_fail
+/// use avl_tree_set_i32::AVLTreeSet;
+///
+/// let iter = {
+///     let set = AVLTreeSet::from([1, 2, 3]);
+///     set.iter()
+/// };
+/// ```
+pub struct AVLTreeSetIn<S: Storage> {
     root: Option<NonNull<Node>>,
+    pool: S::Pool<Node>,
+    phantom: PhantomData<Box<Node>>,
 }
 
-impl AVLTreeSet {
+// NOTE: ノードはBoxと同様に木が排他的に所有する
+unsafe impl<S: Storage> Send for AVLTreeSetIn<S> {}
+unsafe impl<S: Storage> Sync for AVLTreeSetIn<S> {}
+
+impl<S: Storage> AVLTreeSetIn<S> {
     pub fn new() -> Self {
         Self::default()
     }
@@ -34,30 +52,34 @@ impl AVLTreeSet {
     }
 
     pub fn insert(&mut self, value: i32) -> bool {
-        fn insert(node: &mut Option<NonNull<Node>>, value: i32) -> bool {
+        fn insert(
+            node: &mut Option<NonNull<Node>>,
+            value: i32,
+            pool: &mut impl Pool<Node>,
+        ) -> bool {
             if let Some(node) = node.map(|mut node| unsafe { node.as_mut() }) {
                 match value.cmp(&node.value) {
                     Ordering::Equal => {
                         return false;
                     }
                     Ordering::Less => {
-                        if !insert(&mut node.left, value) {
+                        if !insert(&mut node.left, value, pool) {
                             return false;
                         }
                     }
                     Ordering::Greater => {
-                        if !insert(&mut node.right, value) {
+                        if !insert(&mut node.right, value, pool) {
                             return false;
                         }
                     }
                 }
             } else {
-                *node = Some(Node::new(value));
+                *node = Some(Node::new(value, pool));
             }
             Node::balance(node);
             true
         }
-        insert(&mut self.root, value)
+        insert(&mut self.root, value, &mut self.pool)
     }
 
     pub fn contains(&self, value: &i32) -> bool {
@@ -79,19 +101,23 @@ impl AVLTreeSet {
     }
 
     pub fn remove(&mut self, value: &i32) -> bool {
-        fn remove(node: &mut Option<NonNull<Node>>, value: &i32) -> bool {
+        fn remove(
+            node: &mut Option<NonNull<Node>>,
+            value: &i32,
+            pool: &mut impl Pool<Node>,
+        ) -> bool {
             if let Some(x) = node {
                 let x_ref = unsafe { x.as_mut() };
                 match value.cmp(&x_ref.value) {
                     Ordering::Equal => {
                         if x_ref.left.is_none() {
                             let right = x_ref.right;
-                            unsafe { drop(Box::from_raw(x.as_ptr())) };
+                            unsafe { pool.dealloc(*x) };
                             *node = right;
                             return true;
                         } else if x_ref.right.is_none() {
                             let left = x_ref.left;
-                            unsafe { drop(Box::from_raw(x.as_ptr())) };
+                            unsafe { pool.dealloc(*x) };
                             *node = left;
                             return true;
                         } else {
@@ -100,20 +126,20 @@ impl AVLTreeSet {
                                 right = left;
                             }
                             x_ref.value = unsafe { right.as_ref().value };
-                            if remove(&mut x_ref.right, &unsafe { right.as_ref().value }) {
+                            if remove(&mut x_ref.right, &unsafe { right.as_ref().value }, pool) {
                                 Node::balance(node);
                                 return true;
                             }
                         }
                     }
                     Ordering::Less => {
-                        if remove(&mut x_ref.left, value) {
+                        if remove(&mut x_ref.left, value, pool) {
                             Node::balance(node);
                             return true;
                         }
                     }
                     Ordering::Greater => {
-                        if remove(&mut x_ref.right, value) {
+                        if remove(&mut x_ref.right, value, pool) {
                             Node::balance(node);
                             return true;
                         }
@@ -123,7 +149,7 @@ impl AVLTreeSet {
             false
         }
 
-        remove(&mut self.root, value)
+        remove(&mut self.root, value, &mut self.pool)
     }
 
     /// 昇順でn番目の要素を取得する
@@ -160,52 +186,23 @@ impl AVLTreeSet {
         None
     }
 
-    /// NOTE: 2つのAVL木の要素数をN, Mに対してO(min(N+M)log N)
-    pub fn append(&mut self, other: &mut AVLTreeSet) {
-        fn insert(tree: &mut AVLTreeSet, node: Option<NonNull<Node>>) {
-            if let Some(node) = node {
-                insert(tree, unsafe { node.as_ref() }.left);
-                insert(tree, unsafe { node.as_ref() }.right);
-                tree.insert(unsafe { node.as_ref() }.value);
-            }
-        }
-
-        if self.len() >= other.len() {
-            insert(self, take(other).root);
-        } else {
-            insert(other, take(self).root);
-            swap(self, other);
-        }
+    /// otherの全要素をselfに移動する
+    /// NOTE: 2つのAVL木の要素数をN, M(N >= M)に対してO(M log(N/M + 1))
+    pub fn append(&mut self, other: &mut Self) {
+        self.pool.absorb(&mut other.pool);
+        self.root = Node::union(self.root.take(), other.root.take(), &mut self.pool);
     }
 
-    /// NOTE: AVL木の要素数Nに対してO(Nlog N)
-    /// ↑本当？　もっと効率的な方法があるのでは
-    /// 実装も要改善
-    pub fn split_off(&mut self, value: &i32) -> AVLTreeSet {
-        fn insert(
-            node: &mut Option<NonNull<Node>>,
-            left: &mut AVLTreeSet,
-            right: &mut AVLTreeSet,
-            value: &i32,
-        ) {
-            if let Some(mut node) = node.take() {
-                insert(&mut unsafe { node.as_mut() }.left, left, right, value);
-                insert(&mut unsafe { node.as_mut() }.right, left, right, value);
-                let node_value = unsafe { node.as_ref() }.value;
-                if &node_value < value {
-                    left.insert(node_value);
-                } else {
-                    right.insert(node_value);
-                }
-                unsafe { drop(Box::from_raw(node.as_ptr())) };
-            }
+    /// value以上の要素を切り離して返す
+    /// NOTE: AVL木の要素数Nに対してO(log N)
+    pub fn split_off(&mut self, value: &i32) -> Self {
+        let (left, right) = Node::split(self.root.take(), value);
+        self.root = left;
+        Self {
+            root: right,
+            pool: self.pool.share(),
+            phantom: PhantomData,
         }
-
-        let mut left = Self::new();
-        let mut right = Self::new();
-        insert(&mut self.root.take(), &mut left, &mut right, value);
-        *self = left;
-        right
     }
 
     pub fn iter(&self) -> Iter<'_> {
@@ -213,26 +210,41 @@ impl AVLTreeSet {
     }
 }
 
-impl Default for AVLTreeSet {
+impl<S: Storage> Clone for AVLTreeSetIn<S> {
+    fn clone(&self) -> Self {
+        let mut pool = S::Pool::default();
+        AVLTreeSetIn {
+            root: Node::clone_tree(&self.root, &mut pool),
+            pool,
+            phantom: PhantomData,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        Node::clone_tree_from(&mut self.root, &source.root, &mut self.pool);
+    }
+}
+
+impl<S: Storage> Default for AVLTreeSetIn<S> {
     fn default() -> Self {
-        AVLTreeSet { root: None }
+        AVLTreeSetIn {
+            root: None,
+            pool: S::Pool::default(),
+            phantom: PhantomData,
+        }
     }
 }
 
-impl Drop for AVLTreeSet {
+impl<S: Storage> Drop for AVLTreeSetIn<S> {
     fn drop(&mut self) {
-        fn free(node: &mut Option<NonNull<Node>>) {
-            if let Some(mut node) = node.take() {
-                free(&mut unsafe { node.as_mut() }.left);
-                free(&mut unsafe { node.as_mut() }.right);
-                unsafe { drop(Box::from_raw(node.as_ptr())) };
-            }
+        // NOTE: ノードはDropを持たないので，領域ごと解放できる場合は辿らない
+        if <S::Pool<Node> as Pool<Node>>::FREE_EACH {
+            Node::free(&mut self.root, &mut self.pool);
         }
-        free(&mut self.root)
     }
 }
 
-impl<'a> IntoIterator for &'a AVLTreeSet {
+impl<'a, S: Storage> IntoIterator for &'a AVLTreeSetIn<S> {
     type IntoIter = Iter<'a>;
     type Item = &'a i32;
 
@@ -241,7 +253,7 @@ impl<'a> IntoIterator for &'a AVLTreeSet {
     }
 }
 
-impl IntoIterator for AVLTreeSet {
+impl<S: Storage> IntoIterator for AVLTreeSetIn<S> {
     type IntoIter = IntoIter;
     type Item = i32;
 
@@ -261,7 +273,7 @@ impl IntoIterator for AVLTreeSet {
     }
 }
 
-impl From<Vec<i32>> for AVLTreeSet {
+impl<S: Storage> From<Vec<i32>> for AVLTreeSetIn<S> {
     fn from(v: Vec<i32>) -> Self {
         let mut res = Self::new();
         v.into_iter().for_each(|e| {
@@ -271,7 +283,7 @@ impl From<Vec<i32>> for AVLTreeSet {
     }
 }
 
-impl<const N: usize> From<[i32; N]> for AVLTreeSet {
+impl<S: Storage, const N: usize> From<[i32; N]> for AVLTreeSetIn<S> {
     fn from(v: [i32; N]) -> Self {
         let mut res = Self::new();
         v.into_iter().for_each(|e| {
@@ -281,7 +293,7 @@ impl<const N: usize> From<[i32; N]> for AVLTreeSet {
     }
 }
 
-impl Debug for AVLTreeSet {
+impl<S: Storage> Debug for AVLTreeSetIn<S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_set().entries(self.iter()).finish()
     }
@@ -292,6 +304,10 @@ pub struct Iter<'a> {
     stack_right: Vec<&'a NonNull<Node>>,
 }
 
+// NOTE: &AVLTreeSetと同様
+unsafe impl Send for Iter<'_> {}
+unsafe impl Sync for Iter<'_> {}
+
 impl<'a> Iter<'a> {
     fn new(root: &'a Option<NonNull<Node>>) -> Self {
         let mut iter = Self {
@@ -363,15 +379,23 @@ struct Node {
 }
 
 impl Node {
-    fn new(value: i32) -> NonNull<Node> {
-        let node = Node {
+    fn new(value: i32, pool: &mut impl Pool<Node>) -> NonNull<Node> {
+        pool.alloc(Node {
             value,
             len: 1,
             height: 1,
             left: None,
             right: None,
-        };
-        NonNull::from(Box::leak(Box::new(node)))
+        })
+    }
+
+    /// nodeを根とする木の全ノードを解放する
+    fn free(node: &mut Option<NonNull<Node>>, pool: &mut impl Pool<Node>) {
+        if let Some(mut node) = node.take() {
+            Node::free(&mut unsafe { node.as_mut() }.left, pool);
+            Node::free(&mut unsafe { node.as_mut() }.right, pool);
+            unsafe { pool.dealloc(node) };
+        }
     }
 
     fn update(&mut self) {
@@ -387,6 +411,143 @@ impl Node {
         node.map_or(0, |node| unsafe { node.as_ref() }.height)
     }
 
+    /// 木の形をそのままにして複製する
+    fn clone_tree(
+        node: &Option<NonNull<Node>>,
+        pool: &mut impl Pool<Node>,
+    ) -> Option<NonNull<Node>> {
+        node.map(|node| {
+            let node = unsafe { node.as_ref() };
+            let new_node = Node {
+                value: node.value,
+                len: node.len,
+                height: node.height,
+                left: Node::clone_tree(&node.left, pool),
+                right: Node::clone_tree(&node.right, pool),
+            };
+            pool.alloc(new_node)
+        })
+    }
+
+    /// dstをsrcと同じ形の木にする
+    /// 同じ位置にあるノードのメモリは再利用する
+    fn clone_tree_from(
+        dst: &mut Option<NonNull<Node>>,
+        src: &Option<NonNull<Node>>,
+        pool: &mut impl Pool<Node>,
+    ) {
+        match (dst.as_mut(), src) {
+            (Some(x), Some(y)) => {
+                let x = unsafe { x.as_mut() };
+                let y = unsafe { y.as_ref() };
+                x.value = y.value;
+                x.len = y.len;
+                x.height = y.height;
+                Node::clone_tree_from(&mut x.left, &y.left, pool);
+                Node::clone_tree_from(&mut x.right, &y.right, pool);
+            }
+            (None, Some(_)) => *dst = Node::clone_tree(src, pool),
+            (Some(_), None) => Node::free(dst, pool),
+            (None, None) => {}
+        }
+    }
+
+    /// leftの全要素 < rootの値 < rightの全要素 であるような2つの木をrootを介して連結する
+    fn merge_with_root(
+        mut left: Option<NonNull<Node>>,
+        mut root: NonNull<Node>,
+        mut right: Option<NonNull<Node>>,
+    ) -> Option<NonNull<Node>> {
+        let d = Node::height(&left) - Node::height(&right);
+        if d > 1 {
+            let l = unsafe { left.unwrap().as_mut() };
+            l.right = Node::merge_with_root(l.right, root, right);
+            Node::balance(&mut left);
+            left
+        } else if d < -1 {
+            let r = unsafe { right.unwrap().as_mut() };
+            r.left = Node::merge_with_root(left, root, r.left);
+            Node::balance(&mut right);
+            right
+        } else {
+            unsafe { root.as_mut() }.left = left;
+            unsafe { root.as_mut() }.right = right;
+            let mut root = Some(root);
+            Node::balance(&mut root);
+            root
+        }
+    }
+
+    /// valueより小さい要素からなる木，valueと等しいノード，valueより大きい要素からなる木に分割する
+    #[allow(clippy::type_complexity)]
+    fn split3(
+        node: Option<NonNull<Node>>,
+        value: &i32,
+    ) -> (
+        Option<NonNull<Node>>,
+        Option<NonNull<Node>>,
+        Option<NonNull<Node>>,
+    ) {
+        if let Some(mut x) = node {
+            let x_ref = unsafe { x.as_mut() };
+            let left = x_ref.left.take();
+            let right = x_ref.right.take();
+            match value.cmp(&x_ref.value) {
+                Ordering::Equal => {
+                    x_ref.update();
+                    (left, Some(x), right)
+                }
+                Ordering::Less => {
+                    let (l, m, r) = Node::split3(left, value);
+                    (l, m, Node::merge_with_root(r, x, right))
+                }
+                Ordering::Greater => {
+                    let (l, m, r) = Node::split3(right, value);
+                    (Node::merge_with_root(left, x, l), m, r)
+                }
+            }
+        } else {
+            (None, None, None)
+        }
+    }
+
+    /// valueより小さい要素からなる木とvalue以上の要素からなる木に分割する
+    fn split(
+        node: Option<NonNull<Node>>,
+        value: &i32,
+    ) -> (Option<NonNull<Node>>, Option<NonNull<Node>>) {
+        match Node::split3(node, value) {
+            (l, Some(m), r) => (l, Node::merge_with_root(None, m, r)),
+            (l, None, r) => (l, r),
+        }
+    }
+
+    /// 2つの木の和集合となる木
+    /// 同じ値が両方の木に存在する場合は要素数の少ない方のノードを解放する
+    fn union(
+        a: Option<NonNull<Node>>,
+        b: Option<NonNull<Node>>,
+        pool: &mut impl Pool<Node>,
+    ) -> Option<NonNull<Node>> {
+        let (Some(mut x), Some(_)) = (a, b) else {
+            return a.or(b);
+        };
+        if Node::len(&a) < Node::len(&b) {
+            return Node::union(b, a, pool);
+        }
+
+        let x_ref = unsafe { x.as_mut() };
+        let left = x_ref.left.take();
+        let right = x_ref.right.take();
+        let (l, m, r) = Node::split3(b, &x_ref.value);
+        if let Some(m) = m {
+            unsafe { pool.dealloc(m) };
+        }
+        let left = Node::union(left, l, pool);
+        let right = Node::union(right, r, pool);
+        Node::merge_with_root(left, x, right)
+    }
+
     /// 平衡
     fn balance(node: &mut Option<NonNull<Node>>) {
         /// 左部分木と右部分木の高さの差
@@ -422,7 +583,7 @@ impl Node {
         }
 
         if let Some(x) = node {
-            let d = diff_height(&x);
+            let d = diff_height(x);
             let x = unsafe { x.as_mut() };
 
             if d > 1 {
@@ -448,7 +609,7 @@ impl Node {
     }
 }
 
-impl AVLTreeSet {
+impl<S: Storage> AVLTreeSetIn<S> {
     #[cfg(debug_assertions)]
     #[allow(unused)]
     pub fn visualize(&self) -> String {
@@ -490,7 +651,8 @@ impl AVLTreeSet {
 
 #[cfg(test)]
 mod tests {
-    use super::AVLTreeSet;
+    use super::{AVLTreeSet, Node};
+    use std::ptr::NonNull;
 
     #[test]
     fn test_insert_and_contains() {
@@ -512,6 +674,57 @@ mod tests {
         assert!(!tree.contains(&100));
     }
 
+    #[test]
+    fn test_send_sync() {
+        use crate::{ArenaAVLTreeSet, IntoIter, Iter};
+        use std::thread;
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<AVLTreeSet>();
+        assert_send_sync::<Iter<'_>>();
+        assert_send_sync::<IntoIter>();
+        assert_send_sync::<ArenaAVLTreeSet>();
+
+        let tree = thread::spawn(|| AVLTreeSet::from((0..100).collect::<Vec<_>>()))
+            .join()
+            .unwrap();
+        thread::scope(|s| {
+            let mut iter = tree.iter();
+            s.spawn(move || assert_eq!(iter.next_back(), Some(&99)));
+            s.spawn(|| assert_eq!(tree.get_nth(10), Some(&10)));
+        });
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut tree1 = AVLTreeSet::from((0..100).collect::<Vec<_>>());
+        let mut tree2 = tree1.clone();
+        assert!(tree1.iter().eq(tree2.iter()));
+        assert!(is_balanced(&tree2.root));
+
+        assert!(tree1.remove(&10));
+        assert!(tree1.insert(1000));
+        assert!(tree2.remove(&20));
+        assert!(tree2.insert(2000));
+        assert!(!tree1.contains(&10));
+        assert!(tree1.contains(&20));
+        assert!(tree2.contains(&10));
+        assert!(!tree2.contains(&20));
+        drop(tree1);
+        assert_eq!(tree2.len(), 100);
+        drop(tree2);
+
+        let src = AVLTreeSet::from((0..50).collect::<Vec<_>>());
+        for n in [0, 10, 50, 200] {
+            let mut dst = AVLTreeSet::from((0..n).map(|x| x * 7).collect::<Vec<_>>());
+            dst.clone_from(&src);
+            assert!(dst.iter().eq(src.iter()));
+            assert!(is_balanced(&dst.root));
+            dst.insert(-1);
+            assert_eq!(dst.len(), src.len() + 1);
+        }
+    }
+
     #[test]
     fn test_remove() {
         let mut tree = AVLTreeSet::from([52, 73, 63, 27, 44, 94, 31, 82, 70, 37]);
@@ -592,6 +805,52 @@ mod tests {
         let tree2 = tree1.split_off(&5);
         assert!(tree1.iter().copied().eq([2, 4]));
         assert!(tree2.iter().copied().eq([6, 8, 10]));
+
+        let mut tree1 = AVLTreeSet::from([10, 20, 30, 40, 50]);
+        let tree2 = tree1.split_off(&60);
+        assert!(tree1.iter().copied().eq([10, 20, 30, 40, 50]));
+        assert!(tree2.is_empty());
+        let tree3 = tree1.split_off(&0);
+        assert!(tree1.is_empty());
+        assert!(tree3.iter().copied().eq([10, 20, 30, 40, 50]));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_split_off_random() {
+        use rand::{rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = rng();
+
+        for _ in 0..100 {
+            let v = (0..rng.random_range(0..300))
+                .map(|_| rng.random_range(-500..=500))
+                .collect::<Vec<i32>>();
+            let mut avl = AVLTreeSet::from(v.clone());
+            let mut b = BTreeSet::from_iter(v);
+            let x = rng.random_range(-600..=600);
+            let avl2 = avl.split_off(&x);
+            let b2 = b.split_off(&x);
+            assert!(avl.iter().eq(b.iter()));
+            assert!(avl2.iter().eq(b2.iter()));
+            assert_eq!(avl.len(), b.len());
+            assert_eq!(avl2.len(), b2.len());
+            assert!(is_balanced(&avl.root));
+            assert!(is_balanced(&avl2.root));
+        }
+    }
+
+    /// 全てのノードでAVL木の条件が満たされているか
+    fn is_balanced(node: &Option<NonNull<Node>>) -> bool {
+        node.is_none_or(|node| {
+            let node = unsafe { node.as_ref() };
+            (Node::height(&node.left) - Node::height(&node.right)).abs() <= 1
+                && node.height == Node::height(&node.left).max(Node::height(&node.right)) + 1
+                && node.len == Node::len(&node.left) + Node::len(&node.right) + 1
+                && is_balanced(&node.left)
+                && is_balanced(&node.right)
+        })
     }
 
     #[test]
@@ -637,4 +896,84 @@ mod tests {
             assert!(avl.into_iter().eq(b.into_iter()));
         }
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_append_random() {
+        use rand::{rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = rng();
+
+        for _ in 0..100 {
+            let v1 = (0..rng.random_range(0..300))
+                .map(|_| rng.random_range(-500..=500))
+                .collect::<Vec<i32>>();
+            let v2 = (0..rng.random_range(0..300))
+                .map(|_| rng.random_range(-500..=500))
+                .collect::<Vec<i32>>();
+            let mut avl1 = AVLTreeSet::from(v1.clone());
+            let mut avl2 = AVLTreeSet::from(v2.clone());
+            let mut b1 = BTreeSet::from_iter(v1);
+            let mut b2 = BTreeSet::from_iter(v2);
+            avl1.append(&mut avl2);
+            b1.append(&mut b2);
+            assert!(avl1.iter().eq(b1.iter()));
+            assert_eq!(avl1.len(), b1.len());
+            assert!(avl2.is_empty());
+            assert!(is_balanced(&avl1.root));
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_arena_random() {
+        use crate::ArenaAVLTreeSet;
+        use rand::{rng, Rng};
+        use std::collections::BTreeSet;
+
+        let mut rng = rng();
+
+        for _ in 0..5 {
+            let mut avl = ArenaAVLTreeSet::new();
+            let mut b = BTreeSet::new();
+            for _ in 0..1000 {
+                // 0: insert
+                // 1: remove
+                // 2: split_off + append
+                // 3: clone_from
+                let t = rng.random_range(0..4);
+                match t {
+                    0 => {
+                        let x = rng.random_range(-100..=100);
+                        assert_eq!(b.insert(x), avl.insert(x));
+                    }
+                    1 => {
+                        let x = rng.random_range(-100..=100);
+                        assert_eq!(b.remove(&x), avl.remove(&x));
+                    }
+                    2 => {
+                        let x = rng.random_range(-100..=100);
+                        let mut avl2 = avl.split_off(&x);
+                        let mut b2 = b.split_off(&x);
+                        assert!(avl2.iter().eq(b2.iter()));
+                        avl2.insert(x);
+                        b2.insert(x);
+                        avl.append(&mut avl2);
+                        b.append(&mut b2);
+                    }
+                    3 => {
+                        let mut cloned = ArenaAVLTreeSet::from([1, 2, 3]);
+                        cloned.clone_from(&avl);
+                        assert!(cloned.iter().eq(b.iter()));
+                    }
+                    _ => {}
+                }
+                assert_eq!(b.len(), avl.len());
+                assert!(avl.iter().eq(b.iter()));
+                assert!(is_balanced(&avl.root));
+            }
+            assert!(avl.into_iter().eq(b.into_iter()));
+        }
+    }
 }
@@ -0,0 +1,201 @@
+//! AVL木のノードを確保する領域
+//! Boxedはノードごとにヒープ確保し，Arenaはまとめて確保したチャンクからノードを切り出す
+//! どちらもノードはNonNullで参照するので，各木はStorageを型引数に取って同じ実装を使う
+
+use std::{mem::ManuallyDrop, ptr::NonNull, sync::Arc};
+
+/// 木がノードを確保する領域の種類
+pub trait Storage: private::Sealed {
+    #[doc(hidden)]
+    type Pool<N>: Pool<N>;
+}
+
+/// ノードごとにBoxで確保する
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Boxed;
+
+/// ノードをチャンクにまとめて確保し，削除したノードの領域は空きリストで再利用する
+/// Dropやclearでは要素がDropを必要としなければノードを個別に解放しない
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Arena;
+
+impl Storage for Boxed {
+    type Pool<N> = BoxPool;
+}
+
+impl Storage for Arena {
+    type Pool<N> = ArenaPool<N>;
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for super::Boxed {}
+    impl Sealed for super::Arena {}
+}
+
+/// ノードの確保と解放
+#[doc(hidden)]
+pub trait Pool<N>: Default {
+    /// Dropでノードを1つずつ解放する必要があるか
+    const FREE_EACH: bool;
+
+    fn alloc(&mut self, node: N) -> NonNull<N>;
+
+    /// ptrのノードを取り出して領域を解放する
+    ///
+    /// # Safety
+    /// ptrはこの領域(または共有・吸収した領域)から確保した生きているノード
+    unsafe fn dealloc(&mut self, ptr: NonNull<N>) -> N;
+
+    /// otherから確保したノードをselfのノードとして扱えるようにする
+    fn absorb(&mut self, other: &mut Self);
+
+    /// selfから確保したノードを扱える空の領域
+    fn share(&self) -> Self;
+}
+
+#[doc(hidden)]
+#[derive(Default)]
+pub struct BoxPool;
+
+impl<N> Pool<N> for BoxPool {
+    const FREE_EACH: bool = true;
+
+    #[inline]
+    fn alloc(&mut self, node: N) -> NonNull<N> {
+        NonNull::from(Box::leak(Box::new(node)))
+    }
+
+    #[inline]
+    unsafe fn dealloc(&mut self, ptr: NonNull<N>) -> N {
+        *unsafe { Box::from_raw(ptr.as_ptr()) }
+    }
+
+    fn absorb(&mut self, _: &mut Self) {}
+
+    fn share(&self) -> Self {
+        Self
+    }
+}
+
+/// 最初に確保するチャンクの容量
+const MIN_CHUNK: usize = 16;
+
+/// ノードを格納する未初期化の領域
+/// 木を分割すると複数の木から参照されるのでArcで共有する
+struct Chunk<N> {
+    ptr: NonNull<N>,
+    cap: usize,
+}
+
+// NOTE: チャンク自体はノードに触れず，各ノードはいずれか1つの木が排他的に所有する
+unsafe impl<N> Send for Chunk<N> {}
+unsafe impl<N> Sync for Chunk<N> {}
+
+impl<N> Chunk<N> {
+    fn new(cap: usize) -> Self {
+        let mut v = ManuallyDrop::new(Vec::<N>::with_capacity(cap));
+        Self {
+            ptr: NonNull::new(v.as_mut_ptr()).unwrap(),
+            cap: v.capacity(),
+        }
+    }
+}
+
+impl<N> Drop for Chunk<N> {
+    fn drop(&mut self) {
+        // NOTE: 生きているノードは木が先にDropする
+        unsafe { drop(Vec::from_raw_parts(self.ptr.as_ptr(), 0, self.cap)) };
+    }
+}
+
+#[doc(hidden)]
+pub struct ArenaPool<N> {
+    chunks: Vec<Arc<Chunk<N>>>,
+    /// 最後に確保したチャンクのうち未使用の領域の先頭
+    next: NonNull<N>,
+    /// 未使用の領域の長さ
+    rest: usize,
+    /// 次に確保するチャンクの容量
+    cap: usize,
+    /// 解放したノードの領域
+    free: Vec<NonNull<N>>,
+}
+
+unsafe impl<N: Send> Send for ArenaPool<N> {}
+unsafe impl<N: Sync> Sync for ArenaPool<N> {}
+
+impl<N> Default for ArenaPool<N> {
+    fn default() -> Self {
+        Self {
+            chunks: vec![],
+            next: NonNull::dangling(),
+            rest: 0,
+            cap: MIN_CHUNK,
+            free: vec![],
+        }
+    }
+}
+
+impl<N> ArenaPool<N> {
+    #[cold]
+    fn grow(&mut self) {
+        let chunk = Chunk::new(self.cap);
+        self.next = chunk.ptr;
+        self.rest = chunk.cap;
+        self.cap = chunk.cap * 2;
+        self.chunks.push(Arc::new(chunk));
+    }
+}
+
+impl<N> Pool<N> for ArenaPool<N> {
+    const FREE_EACH: bool = false;
+
+    #[inline]
+    fn alloc(&mut self, node: N) -> NonNull<N> {
+        let ptr = match self.free.pop() {
+            Some(ptr) => ptr,
+            None => {
+                if self.rest == 0 {
+                    self.grow();
+                }
+                let ptr = self.next;
+                self.next = unsafe { ptr.add(1) };
+                self.rest -= 1;
+                ptr
+            }
+        };
+        unsafe { ptr.write(node) };
+        ptr
+    }
+
+    #[inline]
+    unsafe fn dealloc(&mut self, ptr: NonNull<N>) -> N {
+        self.free.push(ptr);
+        unsafe { ptr.read() }
+    }
+
+    /// NOTE: チャンクの数に対してO(C log C)
+    fn absorb(&mut self, other: &mut Self) {
+        self.chunks.append(&mut other.chunks);
+        // 分割した木を連結し直した場合に同じチャンクを重複して持たないようにする
+        self.chunks.sort_unstable_by_key(|chunk| chunk.ptr);
+        self.chunks.dedup_by(|a, b| Arc::ptr_eq(a, b));
+        self.free.append(&mut other.free);
+        if other.rest > self.rest {
+            self.next = other.next;
+            self.rest = other.rest;
+        }
+        self.cap = self.cap.max(other.cap);
+        other.rest = 0;
+    }
+
+    /// NOTE: チャンクの数に対してO(C)
+    fn share(&self) -> Self {
+        Self {
+            chunks: self.chunks.clone(),
+            ..Self::default()
+        }
+    }
+}
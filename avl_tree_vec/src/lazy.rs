@@ -0,0 +1,834 @@
+//! 区間の総積の取得ができる列と，それに区間への作用を加えた列の実装
+//! 各ノードは部分木の値の総積と，子に伝播していない作用を持つ
+
+use std::{convert::Infallible, fmt::Debug, marker::PhantomData, ops::RangeBounds, ptr::NonNull};
+
+use crate::{
+    build_from_iter, free, len, merge, merge_with_root, split, split3, to_index_range,
+    traverse_postorder, Augment, BoxPool, Link, Node, NodePtr,
+};
+
+/// 結合的な二項演算opと単位元identityを持つ集合
+pub trait Monoid {
+    type S: Clone;
+    fn identity() -> Self::S;
+    fn op(a: &Self::S, b: &Self::S) -> Self::S;
+}
+
+/// Monoid::Sに作用する写像の集合
+/// mappingは総積に対して分配的である必要がある
+/// (mapping(f, op(a, b)) == op(mapping(f, a), mapping(f, b)))
+pub trait MapMonoid: Monoid {
+    type F: Clone;
+    fn mapping(f: &Self::F, x: &Self::S) -> Self::S;
+
+    /// gの後にfを作用させる写像
+    fn composition(f: &Self::F, g: &Self::F) -> Self::F;
+}
+
+/// 列の要素に遅延して作用させる写像の層
+pub trait Layer<M: Monoid> {
+    type F: Clone;
+    fn mapping(f: &Self::F, x: &M::S) -> M::S;
+
+    /// gの後にfを作用させる写像
+    fn composition(f: &Self::F, g: &Self::F) -> Self::F;
+}
+
+/// 作用を持たない層
+/// 写像の型は空なので作用は生じない
+pub struct NoMap;
+
+impl<M: Monoid> Layer<M> for NoMap {
+    type F = Infallible;
+    fn mapping(f: &Infallible, _: &M::S) -> M::S {
+        match *f {}
+    }
+    fn composition(f: &Infallible, _: &Infallible) -> Infallible {
+        match *f {}
+    }
+}
+
+/// MapMonoidの写像を作用させる層
+pub struct WithMap;
+
+impl<M: MapMonoid> Layer<M> for WithMap {
+    type F = M::F;
+    fn mapping(f: &M::F, x: &M::S) -> M::S {
+        M::mapping(f, x)
+    }
+    fn composition(f: &M::F, g: &M::F) -> M::F {
+        M::composition(f, g)
+    }
+}
+
+/// 部分木の総積と子に伝播していない作用
+/// 作用は自身のvalueとfoldには適用済み
+/// 反転に備えて逆順の総積rev_foldも持つ
+struct Lazy<M: Monoid, L: Layer<M>> {
+    fold: M::S,
+    rev_fold: M::S,
+    lazy: Option<L::F>,
+}
+
+impl<M: Monoid, L: Layer<M>> Clone for Lazy<M, L> {
+    fn clone(&self) -> Self {
+        Self {
+            fold: self.fold.clone(),
+            rev_fold: self.rev_fold.clone(),
+            lazy: self.lazy.clone(),
+        }
+    }
+}
+
+impl<M: Monoid, L: Layer<M>> Augment<M::S> for Lazy<M, L> {
+    fn new(value: &M::S) -> Self {
+        Self {
+            fold: value.clone(),
+            rev_fold: value.clone(),
+            lazy: None,
+        }
+    }
+
+    fn fetch(node: &mut Node<M::S, Self>) {
+        let mut fold = node.value.clone();
+        let mut rev_fold = node.value.clone();
+        if let Some(left) = node.left.map(|left| unsafe { left.as_ref() }) {
+            fold = M::op(&left.aug.fold, &fold);
+            rev_fold = M::op(&rev_fold, &left.aug.rev_fold);
+        }
+        if let Some(right) = node.right.map(|right| unsafe { right.as_ref() }) {
+            fold = M::op(&fold, &right.aug.fold);
+            rev_fold = M::op(&right.aug.rev_fold, &rev_fold);
+        }
+        node.aug.fold = fold;
+        node.aug.rev_fold = rev_fold;
+    }
+
+    fn push(node: &mut Node<M::S, Self>) {
+        if let Some(f) = node.aug.lazy.take() {
+            for child in [node.left, node.right].into_iter().flatten() {
+                apply_node::<M, L>(child, &f);
+            }
+        }
+    }
+
+    fn reverse(node: &mut Node<M::S, Self>) {
+        std::mem::swap(&mut node.aug.fold, &mut node.aug.rev_fold);
+    }
+}
+
+type LazyNode<M, L> = Node<<M as Monoid>::S, Lazy<M, L>>;
+type LazyPtr<M, L> = NodePtr<<M as Monoid>::S, Lazy<M, L>>;
+type LazyLink<M, L> = Link<<M as Monoid>::S, Lazy<M, L>>;
+
+/// 部分木全体にfを作用させる
+fn apply_node<M: Monoid, L: Layer<M>>(mut node: LazyPtr<M, L>, f: &L::F) {
+    let node = unsafe { node.as_mut() };
+    node.value = L::mapping(f, &node.value);
+    node.aug.fold = L::mapping(f, &node.aug.fold);
+    node.aug.rev_fold = L::mapping(f, &node.aug.rev_fold);
+    node.aug.lazy = compose::<M, L>(Some(f), node.aug.lazy.as_ref());
+}
+
+/// gの後にfを作用させる写像(Noneは恒等写像)
+fn compose<M: Monoid, L: Layer<M>>(f: Option<&L::F>, g: Option<&L::F>) -> Option<L::F> {
+    match (f, g) {
+        (Some(f), Some(g)) => Some(L::composition(f, g)),
+        (f, g) => f.or(g).cloned(),
+    }
+}
+
+/// xにfを作用させる(Noneは恒等写像)
+fn map<M: Monoid, L: Layer<M>>(f: Option<&L::F>, x: &M::S) -> M::S {
+    f.map_or_else(|| x.clone(), |f| L::mapping(f, x))
+}
+
+/// 伝播していない反転の偶奇がrevの部分木に作用fを施したときのノードの総積
+fn node_fold<M: Monoid, L: Layer<M>>(node: &LazyNode<M, L>, f: Option<&L::F>, rev: bool) -> M::S {
+    map::<M, L>(
+        f,
+        if rev {
+            &node.aug.rev_fold
+        } else {
+            &node.aug.fold
+        },
+    )
+}
+
+/// 部分木に作用fと偶奇revの反転を施したときの[l, r)の総積
+/// 祖先の作用と反転を持ち回るので木を変更しない
+fn fold_range<M: Monoid, L: Layer<M>>(
+    node: LazyLink<M, L>,
+    l: usize,
+    r: usize,
+    f: Option<&L::F>,
+    rev: bool,
+) -> M::S {
+    let Some(node) = node.map(|node| unsafe { node.as_ref() }) else {
+        return M::identity();
+    };
+    if l >= r {
+        return M::identity();
+    }
+    if l == 0 && r >= node.len {
+        return node_fold::<M, L>(node, f, rev);
+    }
+    let child_f = compose::<M, L>(f, node.aug.lazy.as_ref());
+    let (left, right, child_rev) = node.children(rev);
+    let left_len = len(left);
+    let mut res = M::identity();
+    if l < left_len {
+        res = fold_range::<M, L>(left, l, r.min(left_len), child_f.as_ref(), child_rev);
+    }
+    if l <= left_len && left_len < r {
+        res = M::op(&res, &map::<M, L>(f, &node.value));
+    }
+    if r > left_len + 1 {
+        let rest = fold_range::<M, L>(
+            right,
+            l.saturating_sub(left_len + 1),
+            r - left_len - 1,
+            child_f.as_ref(),
+            child_rev,
+        );
+        res = M::op(&res, &rest);
+    }
+    res
+}
+
+/// 部分木に作用fと偶奇revの反転を施したときの[l, len)について，accに続けて総積をとり
+/// predを満たさなくなる位置を返す(最後まで満たす場合はNone)
+fn max_right_node<M: Monoid, L: Layer<M>>(
+    node: LazyLink<M, L>,
+    l: usize,
+    f: Option<&L::F>,
+    rev: bool,
+    acc: &mut M::S,
+    pred: &mut impl FnMut(&M::S) -> bool,
+) -> Option<usize> {
+    let node = unsafe { node?.as_ref() };
+    if l == 0 {
+        let next = M::op(acc, &node_fold::<M, L>(node, f, rev));
+        if pred(&next) {
+            *acc = next;
+            return None;
+        }
+    }
+    let child_f = compose::<M, L>(f, node.aug.lazy.as_ref());
+    let (left, right, child_rev) = node.children(rev);
+    let left_len = len(left);
+    if l < left_len {
+        if let Some(pos) = max_right_node::<M, L>(left, l, child_f.as_ref(), child_rev, acc, pred) {
+            return Some(pos);
+        }
+    }
+    if l <= left_len {
+        let next = M::op(acc, &map::<M, L>(f, &node.value));
+        if !pred(&next) {
+            return Some(left_len);
+        }
+        *acc = next;
+    }
+    max_right_node::<M, L>(
+        right,
+        l.saturating_sub(left_len + 1),
+        child_f.as_ref(),
+        child_rev,
+        acc,
+        pred,
+    )
+    .map(|pos| pos + left_len + 1)
+}
+
+fn clone_tree<M: Monoid, L: Layer<M>>(node: LazyLink<M, L>) -> LazyLink<M, L> {
+    let node = unsafe { node?.as_ref() };
+    let cloned = LazyNode::<M, L> {
+        value: node.value.clone(),
+        len: node.len,
+        height: node.height,
+        aug: node.aug.clone(),
+        rev: node.rev,
+        left: clone_tree::<M, L>(node.left),
+        right: clone_tree::<M, L>(node.right),
+    };
+    Some(NonNull::from(Box::leak(Box::new(cloned))))
+}
+
+/// AVL木による列に，区間の総積の取得を加えたもの
+/// 区間への作用はLayerとしてWithMapを指定したAvlTreeLazyVecで行える
+pub struct AvlTreeFoldVec<M: Monoid, L: Layer<M> = NoMap> {
+    root: LazyLink<M, L>,
+    phantom: PhantomData<Box<LazyNode<M, L>>>,
+}
+
+/// AVL木による列に，区間の総積の取得と区間への作用を加えたもの
+/// 作用は遅延して子に伝播する
+pub type AvlTreeLazyVec<M> = AvlTreeFoldVec<M, WithMap>;
+
+unsafe impl<M: Monoid, L: Layer<M>> Send for AvlTreeFoldVec<M, L>
+where
+    M::S: Send,
+    L::F: Send,
+{
+}
+unsafe impl<M: Monoid, L: Layer<M>> Sync for AvlTreeFoldVec<M, L>
+where
+    M::S: Sync,
+    L::F: Sync,
+{
+}
+
+impl<M: Monoid, L: Layer<M>> AvlTreeFoldVec<M, L> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn len(&self) -> usize {
+        len(self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// index番目の要素
+    /// NOTE: O(log N)
+    pub fn get(&self, index: usize) -> Option<M::S> {
+        (index < self.len()).then(|| fold_range::<M, L>(self.root, index, index + 1, None, false))
+    }
+
+    /// index番目の要素をvalueに置き換える
+    /// NOTE: O(log N)
+    pub fn set(&mut self, index: usize, value: M::S) {
+        assert!(index < self.len());
+        let (left, right) = split(self.root.take(), index);
+        let (removed, right) = split(right, 1);
+        free(removed.unwrap());
+        self.root = merge_with_root(left, Some(Node::new(value)), right);
+    }
+
+    pub fn push_front(&mut self, value: M::S) {
+        self.insert(0, value);
+    }
+
+    pub fn push_back(&mut self, value: M::S) {
+        self.insert(self.len(), value);
+    }
+
+    pub fn pop_front(&mut self) -> Option<M::S> {
+        self.remove(0)
+    }
+
+    pub fn pop_back(&mut self) -> Option<M::S> {
+        self.remove(self.len().checked_sub(1)?)
+    }
+
+    pub fn insert(&mut self, index: usize, value: M::S) {
+        assert!(index <= self.len());
+        let (left, right) = split(self.root.take(), index);
+        self.root = merge_with_root(left, Some(Node::new(value)), right);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<M::S> {
+        (index < self.len()).then(|| {
+            let (left, right) = split(self.root.take(), index);
+            let (removed, right) = split(right, 1);
+            self.root = merge(left, right);
+            let boxed = unsafe { Box::from_raw(removed.unwrap().as_ptr()) };
+            boxed.value
+        })
+    }
+
+    pub fn append(&mut self, other: &mut Self) {
+        self.root = merge(self.root.take(), other.root.take());
+    }
+
+    pub fn split_off(&mut self, index: usize) -> Self {
+        assert!(index <= self.len());
+        let (left, right) = split(self.root.take(), index);
+        self.root = left;
+        Self {
+            root: right,
+            phantom: PhantomData,
+        }
+    }
+
+    /// 範囲内の要素の総積
+    /// NOTE: O(log N)
+    pub fn fold<R: RangeBounds<usize>>(&self, range: R) -> M::S {
+        let (l, r) = to_index_range(&range, self.len());
+        fold_range::<M, L>(self.root, l, r, None, false)
+    }
+
+    /// 範囲内の要素を反転する
+    /// NOTE: O(log N)
+    pub fn reverse<R: RangeBounds<usize>>(&mut self, range: R) {
+        let (l, r) = to_index_range(&range, self.len());
+        let (left, mid, right) = split3(self.root.take(), l, r);
+        if let Some(mut mid) = mid {
+            unsafe { mid.as_mut() }.reverse();
+        }
+        self.root = merge(merge(left, mid), right);
+    }
+
+    /// pred(fold(l..r))がtrueとなる最大のr
+    /// predは単調でpred(identity)がtrueである必要がある
+    /// NOTE: O(log N)
+    pub fn max_right(&self, l: usize, mut pred: impl FnMut(&M::S) -> bool) -> usize {
+        assert!(l <= self.len());
+        assert!(pred(&M::identity()));
+        let mut acc = M::identity();
+        max_right_node::<M, L>(self.root, l, None, false, &mut acc, &mut pred).unwrap_or(self.len())
+    }
+
+    pub fn iter(&self) -> Iter<'_, M, L> {
+        Iter::new(self.root)
+    }
+}
+
+impl<M: MapMonoid> AvlTreeLazyVec<M> {
+    /// 範囲内の要素にfを作用させる
+    /// NOTE: O(log N)
+    pub fn apply<R: RangeBounds<usize>>(&mut self, range: R, f: M::F) {
+        let (l, r) = to_index_range(&range, self.len());
+        let (left, mid, right) = split3(self.root.take(), l, r);
+        if let Some(mid) = mid {
+            apply_node::<M, WithMap>(mid, &f);
+        }
+        self.root = merge(merge(left, mid), right);
+    }
+}
+
+impl<M: Monoid, L: Layer<M>> Default for AvlTreeFoldVec<M, L> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<M: Monoid, L: Layer<M>> Drop for AvlTreeFoldVec<M, L> {
+    fn drop(&mut self) {
+        traverse_postorder(self.root, free);
+    }
+}
+
+impl<M: Monoid, L: Layer<M>> Clone for AvlTreeFoldVec<M, L> {
+    fn clone(&self) -> Self {
+        Self {
+            root: clone_tree::<M, L>(self.root),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<M: Monoid, L: Layer<M>> Debug for AvlTreeFoldVec<M, L>
+where
+    M::S: Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, M: Monoid, L: Layer<M>> IntoIterator for &'a AvlTreeFoldVec<M, L> {
+    type IntoIter = Iter<'a, M, L>;
+    type Item = M::S;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<M: Monoid, L: Layer<M>> Extend<M::S> for AvlTreeFoldVec<M, L> {
+    fn extend<I: IntoIterator<Item = M::S>>(&mut self, iter: I) {
+        let inserted = build_from_iter(iter, &mut BoxPool);
+        self.root = merge(self.root.take(), inserted);
+    }
+}
+
+impl<M: Monoid, L: Layer<M>> FromIterator<M::S> for AvlTreeFoldVec<M, L> {
+    /// NOTE: O(N)
+    fn from_iter<I: IntoIterator<Item = M::S>>(iter: I) -> Self {
+        Self {
+            root: build_from_iter(iter, &mut BoxPool),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<M: Monoid, L: Layer<M>> From<Vec<M::S>> for AvlTreeFoldVec<M, L> {
+    fn from(v: Vec<M::S>) -> Self {
+        Self::from_iter(v)
+    }
+}
+
+/// 保留中の作用を適用した値を前後から返すイテレータ
+/// 祖先の作用を持ち回るので木を変更しない
+pub struct Iter<'a, M: Monoid, L: Layer<M> = NoMap> {
+    /// ノードと，そのノードに適用すべき祖先の作用と反転の偶奇
+    stack: Vec<Frame<M, L>>,
+    stack_rev: Vec<Frame<M, L>>,
+    /// まだ返していないノード数
+    len: usize,
+    phantom: PhantomData<&'a LazyNode<M, L>>,
+}
+
+type Frame<M, L> = (LazyPtr<M, L>, Option<<L as Layer<M>>::F>, bool);
+
+// NOTE: &AvlTreeFoldVecと同様だが，祖先の作用は複製して持つ
+unsafe impl<M: Monoid, L: Layer<M>> Send for Iter<'_, M, L>
+where
+    M::S: Sync,
+    L::F: Send + Sync,
+{
+}
+unsafe impl<M: Monoid, L: Layer<M>> Sync for Iter<'_, M, L>
+where
+    M::S: Sync,
+    L::F: Sync,
+{
+}
+
+impl<'a, M: Monoid, L: Layer<M>> Iter<'a, M, L> {
+    fn new(root: LazyLink<M, L>) -> Self {
+        let mut iter = Self {
+            stack: vec![],
+            stack_rev: vec![],
+            len: len(root),
+            phantom: PhantomData,
+        };
+        iter.push_left(root, None, false);
+        iter.push_right(root, None, false);
+        iter
+    }
+
+    fn push_left(&mut self, mut node: LazyLink<M, L>, mut f: Option<L::F>, mut rev: bool) {
+        while let Some(n) = node {
+            let raw = unsafe { n.as_ref() };
+            let child_f = compose::<M, L>(f.as_ref(), raw.aug.lazy.as_ref());
+            let (left, _, child_rev) = raw.children(rev);
+            self.stack.push((n, f, rev));
+            (node, f, rev) = (left, child_f, child_rev);
+        }
+    }
+
+    fn push_right(&mut self, mut node: LazyLink<M, L>, mut f: Option<L::F>, mut rev: bool) {
+        while let Some(n) = node {
+            let raw = unsafe { n.as_ref() };
+            let child_f = compose::<M, L>(f.as_ref(), raw.aug.lazy.as_ref());
+            let (_, right, child_rev) = raw.children(rev);
+            self.stack_rev.push((n, f, rev));
+            (node, f, rev) = (right, child_f, child_rev);
+        }
+    }
+}
+
+impl<'a, M: Monoid, L: Layer<M>> Iterator for Iter<'a, M, L> {
+    type Item = M::S;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.len = self.len.checked_sub(1)?;
+        let (node, f, rev) = self.stack.pop()?;
+        let node = unsafe { node.as_ref() };
+        let (_, right, child_rev) = node.children(rev);
+        let child_f = compose::<M, L>(f.as_ref(), node.aug.lazy.as_ref());
+        self.push_left(right, child_f, child_rev);
+        Some(map::<M, L>(f.as_ref(), &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, M: Monoid, L: Layer<M>> DoubleEndedIterator for Iter<'a, M, L> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.len = self.len.checked_sub(1)?;
+        let (node, f, rev) = self.stack_rev.pop()?;
+        let node = unsafe { node.as_ref() };
+        let (left, _, child_rev) = node.children(rev);
+        let child_f = compose::<M, L>(f.as_ref(), node.aug.lazy.as_ref());
+        self.push_right(left, child_f, child_rev);
+        Some(map::<M, L>(f.as_ref(), &node.value))
+    }
+}
+
+impl<'a, M: Monoid, L: Layer<M>> ExactSizeIterator for Iter<'a, M, L> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{AvlTreeFoldVec, AvlTreeLazyVec, MapMonoid, Monoid};
+
+    /// 区間加算・区間和
+    struct AddSum;
+
+    impl Monoid for AddSum {
+        /// (和, 長さ)
+        type S = (i64, i64);
+        fn identity() -> Self::S {
+            (0, 0)
+        }
+        fn op(a: &Self::S, b: &Self::S) -> Self::S {
+            (a.0 + b.0, a.1 + b.1)
+        }
+    }
+
+    impl MapMonoid for AddSum {
+        type F = i64;
+        fn mapping(f: &i64, x: &Self::S) -> Self::S {
+            (x.0 + f * x.1, x.1)
+        }
+        fn composition(f: &i64, g: &i64) -> i64 {
+            f + g
+        }
+    }
+
+    /// 区間chmin・区間最小値
+    struct ChminMin;
+
+    impl Monoid for ChminMin {
+        type S = i64;
+        fn identity() -> i64 {
+            i64::MAX
+        }
+        fn op(a: &i64, b: &i64) -> i64 {
+            *a.min(b)
+        }
+    }
+
+    impl MapMonoid for ChminMin {
+        type F = i64;
+        fn mapping(f: &i64, x: &i64) -> i64 {
+            *f.min(x)
+        }
+        fn composition(f: &i64, g: &i64) -> i64 {
+            *f.min(g)
+        }
+    }
+
+    /// 文字列の連結
+    struct Concat;
+
+    impl Monoid for Concat {
+        type S = String;
+        fn identity() -> String {
+            String::new()
+        }
+        fn op(a: &String, b: &String) -> String {
+            a.clone() + b
+        }
+    }
+
+    #[test]
+    fn test_avl_tree_lazy_vec_add_sum() {
+        let mut v: AvlTreeLazyVec<AddSum> = (1..=5).map(|x| (x, 1)).collect();
+        assert_eq!(v.fold(..).0, 15);
+        assert_eq!(v.fold(1..3).0, 5);
+        v.apply(1..4, 10);
+        assert!(v.iter().map(|x| x.0).eq([1, 12, 13, 14, 5]));
+        v.insert(2, (100, 1));
+        assert_eq!(v.fold(1..4).0, 125);
+        v.apply(.., -1);
+        assert_eq!(v.remove(0), Some((0, 1)));
+        assert_eq!(v.get(1), Some((99, 1)));
+        v.set(1, (0, 1));
+        assert!(v.iter().map(|x| x.0).eq([11, 0, 12, 13, 4]));
+        assert_eq!(v.max_right(0, |x| x.0 <= 23), 3);
+        assert_eq!(v.max_right(2, |x| x.0 <= 100), 5);
+        assert_eq!(v.max_right(5, |x| x.0 <= 0), 5);
+
+        let w = v.split_off(2);
+        assert!(v.clone().iter().map(|x| x.0).eq([11, 0]));
+        assert_eq!(w.fold(..).0, 29);
+    }
+
+    #[test]
+    fn test_avl_tree_lazy_vec_chmin_min() {
+        let mut v = AvlTreeLazyVec::<ChminMin>::from(vec![5, 3, 8, 6, 9, 7]);
+        v.apply(2..5, 4);
+        assert!(v.iter().eq([5, 3, 4, 4, 4, 7]));
+        assert_eq!(v.fold(4..), 4);
+        assert_eq!(v.max_right(0, |&x| x >= 4), 1);
+        assert_eq!(v.max_right(2, |&x| x >= 4), 6);
+        assert_eq!(format!("{:?}", v), "[5, 3, 4, 4, 4, 7]");
+    }
+
+    #[test]
+    fn test_avl_tree_lazy_vec_reverse() {
+        let mut v: AvlTreeFoldVec<Concat> = "abcdefg".chars().map(String::from).collect();
+        v.reverse(1..5);
+        assert_eq!(v.fold(..), "aedcbfg");
+        assert_eq!(v.fold(2..6), "dcbf");
+        v.reverse(..);
+        assert_eq!(v.fold(..), "gfbcdea");
+        assert_eq!(v.get(2).as_deref(), Some("b"));
+        assert_eq!(v.max_right(1, |s| !s.contains('d')), 4);
+        assert!(v.clone().iter().eq(["g", "f", "b", "c", "d", "e", "a"]));
+
+        let mut v: AvlTreeLazyVec<AddSum> = (1..=6).map(|x| (x, 1)).collect();
+        v.apply(..3, 10);
+        v.reverse(1..5);
+        v.apply(3.., 100);
+        assert!(v.iter().map(|x| x.0).eq([11, 5, 4, 113, 112, 106]));
+        assert!(v.iter().rev().map(|x| x.0).eq([106, 112, 113, 4, 5, 11]));
+
+        // 前後から交互に取り出しても保留中の作用と反転が反映される
+        let mut iter = v.iter();
+        assert_eq!(iter.len(), 6);
+        assert_eq!(iter.next_back(), Some((106, 1)));
+        assert_eq!(iter.next(), Some((11, 1)));
+        assert_eq!(iter.next_back(), Some((112, 1)));
+        assert_eq!(iter.len(), 3);
+        assert!(iter.map(|x| x.0).eq([5, 4, 113]));
+    }
+
+    #[test]
+    fn test_avl_tree_lazy_vec_extend_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut v: AvlTreeLazyVec<AddSum> = (1..=5).map(|x| (x, 1)).collect();
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            v.extend((6..=10).map(|x| {
+                assert!(x < 8);
+                (x, 1)
+            }))
+        }));
+        assert!(res.is_err());
+        // 既存の木は失われない
+        assert_eq!(v.fold(..), (15, 5));
+        v.extend([(6, 1)]);
+        assert!(v.iter().map(|x| x.0).eq(1..=6));
+    }
+
+    #[test]
+    fn test_avl_tree_lazy_vec_send_sync() {
+        use super::{Iter, WithMap};
+        use std::thread;
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<AvlTreeFoldVec<Concat>>();
+        assert_send_sync::<Iter<'_, Concat>>();
+        assert_send_sync::<AvlTreeLazyVec<AddSum>>();
+        assert_send_sync::<Iter<'_, AddSum, WithMap>>();
+
+        let mut v: AvlTreeLazyVec<AddSum> = (1..=5).map(|x| (x, 1)).collect();
+        v.apply(1..3, 10);
+        thread::scope(|s| {
+            let iter = v.iter();
+            s.spawn(move || assert_eq!(iter.map(|x| x.0).sum::<i64>(), 35));
+        });
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_lazy_vec_random() {
+        use rand::{thread_rng, Rng};
+
+        let mut rng = thread_rng();
+
+        for _ in 0..5 {
+            let mut lazy = AvlTreeLazyVec::<AddSum>::new();
+            let mut v: Vec<i64> = vec![];
+            for _ in 0..1000 {
+                // 0: insert
+                // 1: remove
+                // 2: apply
+                // 3: fold
+                // 4: max_right
+                // 5: reverse
+                let t = rng.gen_range(0..6);
+                let l = rng.gen_range(0..=v.len());
+                let r = rng.gen_range(l..=v.len());
+                match t {
+                    0 => {
+                        let x = rng.gen_range(0..=100);
+                        lazy.insert(l, (x, 1));
+                        v.insert(l, x);
+                    }
+                    1 => {
+                        let expected = (l < v.len()).then(|| (v.remove(l), 1));
+                        assert_eq!(lazy.remove(l), expected);
+                    }
+                    2 => {
+                        let x = rng.gen_range(0..=100);
+                        lazy.apply(l..r, x);
+                        v[l..r].iter_mut().for_each(|e| *e += x);
+                    }
+                    3 => assert_eq!(lazy.fold(l..r).0, v[l..r].iter().sum::<i64>()),
+                    4 => {
+                        let bound = rng.gen_range(0..=1000);
+                        let expected = (l..=v.len())
+                            .take_while(|&r| v[l..r].iter().sum::<i64>() <= bound)
+                            .last()
+                            .unwrap();
+                        assert_eq!(lazy.max_right(l, |x| x.0 <= bound), expected);
+                    }
+                    5 => {
+                        lazy.reverse(l..r);
+                        v[l..r].reverse();
+                    }
+                    _ => {}
+                }
+                assert_eq!(lazy.len(), v.len());
+            }
+            assert!(lazy.iter().map(|x| x.0).eq(v.iter().copied()));
+            assert!(lazy.iter().rev().map(|x| x.0).eq(v.iter().rev().copied()));
+        }
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_avl_tree_lazy_vec_reverse_random() {
+        use rand::{thread_rng, Rng};
+
+        let mut rng = thread_rng();
+
+        for _ in 0..5 {
+            let mut lazy = AvlTreeFoldVec::<Concat>::new();
+            let mut v: Vec<char> = vec![];
+            for _ in 0..500 {
+                // 0: insert
+                // 1: reverse
+                // 2: fold
+                let t = rng.gen_range(0..3);
+                let l = rng.gen_range(0..=v.len());
+                let r = rng.gen_range(l..=v.len());
+                match t {
+                    0 => {
+                        let c = rng.gen_range('a'..='z');
+                        lazy.insert(l, c.to_string());
+                        v.insert(l, c);
+                    }
+                    1 => {
+                        lazy.reverse(l..r);
+                        v[l..r].reverse();
+                    }
+                    2 => assert_eq!(lazy.fold(l..r), v[l..r].iter().collect::<String>()),
+                    _ => {}
+                }
+            }
+            assert!(lazy.iter().eq(v.iter().map(|c| c.to_string())));
+
+            let mut iter = lazy.iter();
+            let mut expected = v.iter().map(|c| c.to_string());
+            while iter.len() > 0 {
+                if rng.gen_bool(0.5) {
+                    assert_eq!(iter.next(), expected.next());
+                } else {
+                    assert_eq!(iter.next_back(), expected.next_back());
+                }
+                assert_eq!(iter.len(), expected.len());
+            }
+            assert_eq!(iter.next(), None);
+        }
+    }
+}
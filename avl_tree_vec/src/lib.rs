@@ -3,60 +3,170 @@ use std::{
     fmt::Debug,
     hash::Hash,
     marker::PhantomData,
-    ops::{Index, IndexMut},
+    mem::{needs_drop, take},
+    ops::{Bound, Index, IndexMut, RangeBounds},
     ptr::NonNull,
 };
 
-struct Node<T> {
+pub mod lazy;
+
+pub use avl_tree_storage::{Arena, Boxed, Storage};
+pub use lazy::{AvlTreeFoldVec, AvlTreeLazyVec, MapMonoid, Monoid};
+
+use avl_tree_storage::{BoxPool, Pool};
+
+/// 部分木から計算してノードに持たせる値
+/// 子に遅延して伝播する作用を持たせることもできる
+trait Augment<T>: Sized {
+    fn new(value: &T) -> Self;
+
+    /// 自身の値と子から再計算する
+    fn fetch(node: &mut Node<T, Self>);
+
+    /// 保留中の作用を子に伝播する
+    fn push(node: &mut Node<T, Self>);
+
+    /// 子を入れ替えて部分木を反転したときに呼び出す
+    fn reverse(node: &mut Node<T, Self>);
+}
+
+impl<T> Augment<T> for () {
+    fn new(_: &T) -> Self {}
+
+    #[inline]
+    fn fetch(_: &mut Node<T, Self>) {}
+
+    #[inline]
+    fn push(_: &mut Node<T, Self>) {}
+
+    #[inline]
+    fn reverse(_: &mut Node<T, Self>) {}
+}
+
+struct Node<T, A = ()> {
     value: T,
     len: usize,
     height: i32,
-    left: Link<T>,
-    right: Link<T>,
+    aug: A,
+    /// 子の部分木の反転を伝播していないか
+    /// 自身の子の入れ替えとaugへの反映は済んでいる
+    rev: bool,
+    left: Link<T, A>,
+    right: Link<T, A>,
 }
 
-type NodePtr<T> = NonNull<Node<T>>;
-type Link<T> = Option<NodePtr<T>>;
+type NodePtr<T, A = ()> = NonNull<Node<T, A>>;
+type Link<T, A = ()> = Option<NodePtr<T, A>>;
 
-impl<T> Node<T> {
-    fn new(value: T) -> NodePtr<T> {
-        let node = Self {
+impl<T, A: Augment<T>> Node<T, A> {
+    fn new(value: T) -> NodePtr<T, A> {
+        Self::new_in(value, &mut BoxPool)
+    }
+
+    /// poolから確保したノード
+    fn new_in(value: T, pool: &mut impl Pool<Self>) -> NodePtr<T, A> {
+        pool.alloc(Self {
+            aug: A::new(&value),
             value,
             len: 1,
             height: 1,
+            rev: false,
             left: None,
             right: None,
-        };
-        NonNull::from(Box::leak(Box::new(node)))
+        })
     }
 
     #[inline]
     fn fetch(&mut self) {
         self.len = len(self.left) + len(self.right) + 1;
         self.height = height(self.left).max(height(self.right)) + 1;
+        A::fetch(self);
+    }
+
+    /// 子を付け替える前に呼び出す
+    #[inline]
+    fn push(&mut self) {
+        if self.rev {
+            for mut child in [self.left, self.right].into_iter().flatten() {
+                unsafe { child.as_mut() }.reverse();
+            }
+            self.rev = false;
+        }
+        A::push(self);
+    }
+
+    /// 部分木を反転する
+    /// NOTE: O(1)
+    #[inline]
+    fn reverse(&mut self) {
+        std::mem::swap(&mut self.left, &mut self.right);
+        self.rev ^= true;
+        A::reverse(self);
+    }
+}
+
+impl<T, A> Node<T, A> {
+    /// 祖先から伝播していない反転の偶奇がrevのときの(左の子, 右の子, 子に伝播していない反転の偶奇)
+    #[inline]
+    fn children(&self, rev: bool) -> (Link<T, A>, Link<T, A>, bool) {
+        if rev {
+            (self.right, self.left, !self.rev)
+        } else {
+            (self.left, self.right, self.rev)
+        }
     }
 }
 
 #[inline]
-fn free<T>(node: NodePtr<T>) {
+fn free<T, A>(node: NodePtr<T, A>) {
     unsafe { drop(Box::from_raw(node.as_ptr())) };
 }
 
+/// rootを根とする木の全ノードを解放する
+/// poolが領域をまとめて解放できる場合は要素のDropだけを行う
+fn free_tree<T, A, P: Pool<Node<T, A>>>(root: Link<T, A>, pool: &mut P) {
+    if P::FREE_EACH {
+        traverse_postorder(root, |node| drop(unsafe { pool.dealloc(node) }));
+    } else if needs_drop::<Node<T, A>>() {
+        traverse_postorder(root, |node| unsafe { node.as_ptr().drop_in_place() });
+    }
+}
+
 #[inline]
-fn len<T>(node: Link<T>) -> usize {
+fn len<T, A>(node: Link<T, A>) -> usize {
     node.map_or(0, |node| unsafe { node.as_ref() }.len)
 }
 
 #[inline]
-fn height<T>(node: Link<T>) -> i32 {
+fn height<T, A>(node: Link<T, A>) -> i32 {
     node.map_or(0, |node| unsafe { node.as_ref() }.height)
 }
 
+/// 範囲を[l, r)の組に変換する
+///
+/// # Panics
+/// 範囲が不正な場合や列の長さを超える場合
+fn to_index_range<R: RangeBounds<usize>>(range: &R, len: usize) -> (usize, usize) {
+    let l = match range.start_bound() {
+        Bound::Included(&l) => l,
+        Bound::Excluded(&l) => l.checked_add(1).expect("range start overflow"),
+        Bound::Unbounded => 0,
+    };
+    let r = match range.end_bound() {
+        Bound::Included(&r) => r.checked_add(1).expect("range end overflow"),
+        Bound::Excluded(&r) => r,
+        Bound::Unbounded => len,
+    };
+    assert!(l <= r, "range start {l} is greater than end {r}");
+    assert!(r <= len, "range end {r} is out of bounds for length {len}");
+    (l, r)
+}
+
 /// 平衡係数
 /// 左部分木と右部分木の高さの差
 /// 左部分木の高さ - 右部分木の高さ
 #[inline]
-fn diff_height<T>(node: Link<T>) -> i32 {
+fn diff_height<T, A>(node: Link<T, A>) -> i32 {
     node.map_or(0, |node| {
         let node = unsafe { node.as_ref() };
         height(node.left) - height(node.right)
@@ -64,17 +174,20 @@ fn diff_height<T>(node: Link<T>) -> i32 {
 }
 
 /// 木を平衡して新たなrootを返す
-fn balance<T>(mut root: Link<T>) -> Link<T> {
+#[allow(clippy::question_mark)]
+fn balance<T, A: Augment<T>>(mut root: Link<T, A>) -> Link<T, A> {
     /// rootを根とした部分木を右回転させる
     /// (左の子が存在している場合のみ呼び出す)
-    fn rotate_right<T>(root: &mut Link<T>) {
+    fn rotate_right<T, A: Augment<T>>(root: &mut Link<T, A>) {
         *root = {
             unsafe {
                 let mut root = root.unwrap();
                 let raw_root = root.as_mut();
+                raw_root.push();
 
                 let mut left = raw_root.left.unwrap();
                 let raw_left = left.as_mut();
+                raw_left.push();
 
                 raw_root.left = raw_left.right;
                 raw_root.fetch();
@@ -87,11 +200,13 @@ fn balance<T>(mut root: Link<T>) -> Link<T> {
 
     /// rootを根とした部分木を左回転させる
     /// (右の子が存在する場合のみ呼び出す)
-    fn rotate_left<T>(root: &mut Link<T>) {
+    fn rotate_left<T, A: Augment<T>>(root: &mut Link<T, A>) {
         *root = {
             unsafe {
                 let mut root = root.unwrap();
+                root.as_mut().push();
                 let mut right = root.as_ref().right.unwrap();
+                right.as_mut().push();
                 root.as_mut().right = right.as_mut().left;
                 root.as_mut().fetch();
                 right.as_mut().left = Some(root);
@@ -104,6 +219,7 @@ fn balance<T>(mut root: Link<T>) -> Link<T> {
     if root.is_none() {
         return None;
     }
+    unsafe { root.unwrap().as_mut() }.push();
 
     let d = diff_height(root);
 
@@ -111,6 +227,7 @@ fn balance<T>(mut root: Link<T>) -> Link<T> {
         // 左部分木が高い場合
 
         let left = &mut unsafe { root.unwrap().as_mut() }.left;
+        unsafe { left.unwrap().as_mut() }.push();
         if diff_height(*left) < 0 {
             rotate_left(left);
         }
@@ -120,6 +237,7 @@ fn balance<T>(mut root: Link<T>) -> Link<T> {
         // 右部分木が高い場合
 
         let right = &mut unsafe { root.unwrap().as_mut() }.right;
+        unsafe { right.unwrap().as_mut() }.push();
         if diff_height(*right) > 0 {
             rotate_right(right);
         }
@@ -132,15 +250,21 @@ fn balance<T>(mut root: Link<T>) -> Link<T> {
     root
 }
 
-fn merge_with_root<T>(left: Link<T>, root: Link<T>, right: Link<T>) -> Link<T> {
+fn merge_with_root<T, A: Augment<T>>(
+    left: Link<T, A>,
+    root: Link<T, A>,
+    right: Link<T, A>,
+) -> Link<T, A> {
     let d = height(left) - height(right);
 
     if d > 1 {
         let raw_left = unsafe { left.unwrap().as_mut() };
+        raw_left.push();
         raw_left.right = merge_with_root(raw_left.right, root, right);
         balance(left)
     } else if d < -1 {
         let raw_right = unsafe { right.unwrap().as_mut() };
+        raw_right.push();
         raw_right.left = merge_with_root(left, root, raw_right.left);
         balance(right)
     } else {
@@ -152,10 +276,11 @@ fn merge_with_root<T>(left: Link<T>, root: Link<T>, right: Link<T>) -> Link<T> {
 }
 
 /// 2つの木をマージして新たなrootを返す
-fn merge<T>(left: Link<T>, right: Link<T>) -> Link<T> {
+fn merge<T, A: Augment<T>>(left: Link<T, A>, right: Link<T, A>) -> Link<T, A> {
     /// nodeの部分木のうち最も右のノードを削除して新たなrootと削除されたノードを返す
-    fn remove_max<T>(mut node: Link<T>) -> (Link<T>, Link<T>) {
+    fn remove_max<T, A: Augment<T>>(mut node: Link<T, A>) -> (Link<T, A>, Link<T, A>) {
         let raw_node = unsafe { node.unwrap().as_mut() };
+        raw_node.push();
         if raw_node.right.is_some() {
             let (tmp, removed) = remove_max(raw_node.right);
             raw_node.right = tmp;
@@ -179,13 +304,14 @@ fn merge<T>(left: Link<T>, right: Link<T>) -> Link<T> {
 }
 
 /// [0, index)の部分木と[index, n)の部分木に分割する
-fn split<T>(root: Link<T>, index: usize) -> (Link<T>, Link<T>) {
+fn split<T, A: Augment<T>>(root: Link<T, A>, index: usize) -> (Link<T, A>, Link<T, A>) {
     if root.is_none() {
         return (None, None);
     }
 
     let (left, right) = {
         let raw_root = unsafe { root.unwrap().as_mut() };
+        raw_root.push();
         let left = raw_root.left;
         let right = raw_root.right;
         raw_root.left = None;
@@ -205,16 +331,61 @@ fn split<T>(root: Link<T>, index: usize) -> (Link<T>, Link<T>) {
     }
 }
 
+/// 列の順に並んだlen個のノードから完全にバランスした木を構築する
+/// NOTE: O(N)
+fn build<T, A: Augment<T>>(
+    nodes: &mut impl Iterator<Item = NodePtr<T, A>>,
+    len: usize,
+) -> Link<T, A> {
+    if len == 0 {
+        return None;
+    }
+    let left = build(nodes, len / 2);
+    let mut root = nodes.next().unwrap();
+    let right = build(nodes, len - len / 2 - 1);
+    let raw_root = unsafe { root.as_mut() };
+    raw_root.left = left;
+    raw_root.right = right;
+    raw_root.fetch();
+    Some(root)
+}
+
+/// iterの要素を順に並べた完全にバランスした木を構築する
+/// iterがパニックしてもノードがリークしないように，要素を集めてからノードを確保する
+/// NOTE: O(N)
+fn build_from_iter<T, A: Augment<T>>(
+    iter: impl IntoIterator<Item = T>,
+    pool: &mut impl Pool<Node<T, A>>,
+) -> Link<T, A> {
+    let values: Vec<_> = iter.into_iter().collect();
+    let len = values.len();
+    build(
+        &mut values.into_iter().map(|value| Node::new_in(value, pool)),
+        len,
+    )
+}
+
+/// [0, l), [l, r), [r, n)の部分木に分割する
+fn split3<T, A: Augment<T>>(
+    root: Link<T, A>,
+    l: usize,
+    r: usize,
+) -> (Link<T, A>, Link<T, A>, Link<T, A>) {
+    let (left, rest) = split(root, l);
+    let (mid, right) = split(rest, r - l);
+    (left, mid, right)
+}
+
 /// index番目のノードを取得する
-fn get<T>(root: Link<T>, index: usize) -> Link<T> {
-    let raw_root = unsafe { root?.as_mut() };
-    let left = raw_root.left;
-    let right = raw_root.right;
+/// 伝播していない反転は偶奇revとして持ち回る
+fn get<T>(root: Link<T>, index: usize, rev: bool) -> Link<T> {
+    let raw_root = unsafe { root?.as_ref() };
+    let (left, right, rev) = raw_root.children(rev);
     let left_len = len(left);
     if index < left_len {
-        get(left, index)
+        get(left, index, rev)
     } else if index > left_len {
-        get(right, index - left_len - 1)
+        get(right, index - left_len - 1, rev)
     } else {
         root
     }
@@ -222,36 +393,36 @@ fn get<T>(root: Link<T>, index: usize) -> Link<T> {
 
 /// はじめてfがfalseとなるindexを返す
 /// すべての要素がtrueの場合はnを返す
-fn bisect<T>(root: Link<T>, mut f: impl FnMut(&T) -> bool) -> usize {
+/// 伝播していない反転は偶奇revとして持ち回る
+fn bisect<T>(root: Link<T>, mut f: impl FnMut(&T) -> bool, rev: bool) -> usize {
     let node = if let Some(node) = root {
         unsafe { node.as_ref() }
     } else {
         return 0;
     };
 
-    let left = node.left;
-    let right = node.right;
+    let (left, right, rev) = node.children(rev);
     let left_len = len(left);
 
     if !f(&node.value) {
-        bisect(left, f)
+        bisect(left, f, rev)
     } else {
-        bisect(right, f) + left_len + 1
+        bisect(right, f, rev) + left_len + 1
     }
 }
 
 #[allow(unused)]
-fn traverse<T>(
-    node: Link<T>,
-    mut preorder_f: impl FnMut(NodePtr<T>),
-    mut inorder_f: impl FnMut(NodePtr<T>),
-    mut postorder_f: impl FnMut(NodePtr<T>),
+fn traverse<T, A>(
+    node: Link<T, A>,
+    mut preorder_f: impl FnMut(NodePtr<T, A>),
+    mut inorder_f: impl FnMut(NodePtr<T, A>),
+    mut postorder_f: impl FnMut(NodePtr<T, A>),
 ) {
-    fn dfs<T>(
-        node: Link<T>,
-        preorder_f: &mut impl FnMut(NodePtr<T>),
-        inorder_f: &mut impl FnMut(NodePtr<T>),
-        postorder_f: &mut impl FnMut(NodePtr<T>),
+    fn dfs<T, A>(
+        node: Link<T, A>,
+        preorder_f: &mut impl FnMut(NodePtr<T, A>),
+        inorder_f: &mut impl FnMut(NodePtr<T, A>),
+        postorder_f: &mut impl FnMut(NodePtr<T, A>),
     ) {
         if let Some(node) = node {
             let left = unsafe { node.as_ref() }.left;
@@ -269,27 +440,62 @@ fn traverse<T>(
 
 #[allow(unused)]
 #[inline]
-fn traverse_preorder<T>(node: Link<T>, f: impl FnMut(NodePtr<T>)) {
+fn traverse_preorder<T, A>(node: Link<T, A>, f: impl FnMut(NodePtr<T, A>)) {
     traverse(node, f, |_| {}, |_| {});
 }
 
 #[allow(unused)]
 #[inline]
-fn traverse_inorder<T>(node: Link<T>, f: impl FnMut(NodePtr<T>)) {
+fn traverse_inorder<T, A>(node: Link<T, A>, f: impl FnMut(NodePtr<T, A>)) {
     traverse(node, |_| {}, f, |_| {});
 }
 
 #[allow(unused)]
 #[inline]
-fn traverse_postorder<T>(node: Link<T>, f: impl FnMut(NodePtr<T>)) {
+fn traverse_postorder<T, A>(node: Link<T, A>, f: impl FnMut(NodePtr<T, A>)) {
     traverse(node, |_| {}, |_| {}, f);
 }
 
-pub struct AvlTreeVec<T> {
+/// ノードごとにBoxで確保するAvlTreeVecIn
+pub type AvlTreeVec<T> = AvlTreeVecIn<T, Boxed>;
+
+/// ノードをまとめて確保した領域に格納するAvlTreeVecIn
+/// 公開APIはAvlTreeVecと同じ
+pub type ArenaAvlTreeVec<T> = AvlTreeVecIn<T, Arena>;
+
+/// ノードの領域をSで指定するAVL木による列
+///
+/// ```compile_fail
+/// use avl_tree_vec::AvlTreeVec;
+///
+/// let iter = {
+///     let v = AvlTreeVec::from([1, 2, 3]);
+///     v.iter()
+/// };
+/// ```
+///
+/// IterMutはTについて不変
+///
+/// ```compile_fail
+/// use avl_tree_vec::IterMut;
+///
+/// fn shorten<'a>(iter: IterMut<'a, &'static str>) -> IterMut<'a, &'a str> {
+///     iter
+/// }
+/// ```
+pub struct AvlTreeVecIn<T, S: Storage> {
     root: Link<T>,
+    pool: NodePool<T, S>,
+    phantom: PhantomData<Box<Node<T>>>,
 }
 
-impl<T> AvlTreeVec<T> {
+type NodePool<T, S> = <S as Storage>::Pool<Node<T>>;
+
+// NOTE: ノードはBoxと同様に木が排他的に所有する
+unsafe impl<T: Send, S: Storage> Send for AvlTreeVecIn<T, S> {}
+unsafe impl<T: Sync, S: Storage> Sync for AvlTreeVecIn<T, S> {}
+
+impl<T, S: Storage> AvlTreeVecIn<T, S> {
     pub fn new() -> Self {
         Self::default()
     }
@@ -307,11 +513,11 @@ impl<T> AvlTreeVec<T> {
     }
 
     pub fn get(&self, index: usize) -> Option<&T> {
-        Some(&unsafe { get(self.root, index)?.as_ref() }.value)
+        Some(&unsafe { get(self.root, index, false)?.as_ref() }.value)
     }
 
     pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
-        Some(&mut unsafe { get(self.root, index)?.as_mut() }.value)
+        Some(&mut unsafe { get(self.root, index, false)?.as_mut() }.value)
     }
 
     pub fn front(&self) -> Option<&T> {
@@ -348,22 +554,37 @@ impl<T> AvlTreeVec<T> {
 
     pub fn insert(&mut self, index: usize, value: T) {
         assert!(index <= self.len());
-        let new_node = Some(Node::new(value));
+        let new_node = Some(Node::new_in(value, &mut self.pool));
         let (left, right) = split(self.root.take(), index);
         self.root = merge_with_root(left, new_node, right);
     }
 
+    /// index番目の位置にiterの要素を順に挿入する
+    /// NOTE: 挿入する要素数Kに対してO(log N + K)
+    pub fn insert_many<I: IntoIterator<Item = T>>(&mut self, index: usize, iter: I) {
+        assert!(index <= self.len());
+        let inserted = build_from_iter(iter, &mut self.pool);
+        let (left, right) = split(self.root.take(), index);
+        self.root = merge(merge(left, inserted), right);
+    }
+
+    /// index番目の位置にvの要素を順に挿入する
+    /// NOTE: 挿入する要素数Kに対してO(log N + K)
+    pub fn insert_vec(&mut self, index: usize, v: Vec<T>) {
+        self.insert_many(index, v);
+    }
+
     pub fn remove(&mut self, index: usize) -> Option<T> {
         (index < self.len()).then(|| {
             let (left, right) = split(self.root.take(), index);
             let (removed, right) = split(right, 1);
             self.root = merge(left, right);
-            let boxed = unsafe { Box::from_raw(removed.unwrap().as_ptr()) };
-            boxed.value
+            unsafe { self.pool.dealloc(removed.unwrap()) }.value
         })
     }
 
     pub fn append(&mut self, other: &mut Self) {
+        self.pool.absorb(&mut other.pool);
         self.root = merge(self.root.take(), other.root.take())
     }
 
@@ -371,11 +592,65 @@ impl<T> AvlTreeVec<T> {
         assert!(index <= self.len());
         let (left, right) = split(self.root.take(), index);
         self.root = left;
-        Self { root: right }
+        Self {
+            root: right,
+            pool: self.pool.share(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// 範囲内の要素を反転する
+    /// NOTE: O(log N)
+    pub fn reverse<R: RangeBounds<usize>>(&mut self, range: R) {
+        let (l, r) = to_index_range(&range, self.len());
+        let (left, mid, right) = split3(self.root.take(), l, r);
+        if let Some(mut mid) = mid {
+            unsafe { mid.as_mut() }.reverse();
+        }
+        self.root = merge(merge(left, mid), right);
+    }
+
+    /// 範囲内の要素を左にk個回転する
+    /// 範囲の先頭からk個の要素が範囲の末尾に移動する
+    /// NOTE: O(log N)
+    ///
+    /// # Panics
+    /// kが範囲の長さより大きい場合
+    pub fn rotate_left<R: RangeBounds<usize>>(&mut self, range: R, k: usize) {
+        let (l, r) = to_index_range(&range, self.len());
+        assert!(k <= r - l);
+        let (left, mid, right) = split3(self.root.take(), l, r);
+        let (head, tail) = split(mid, k);
+        self.root = merge(merge(left, tail), merge(head, right));
+    }
+
+    /// 範囲内の要素を右にk個回転する
+    /// 範囲の末尾からk個の要素が範囲の先頭に移動する
+    /// NOTE: O(log N)
+    ///
+    /// # Panics
+    /// kが範囲の長さより大きい場合
+    pub fn rotate_right<R: RangeBounds<usize>>(&mut self, range: R, k: usize) {
+        let (l, r) = to_index_range(&range, self.len());
+        assert!(k <= r - l);
+        self.rotate_left(l..r, r - l - k);
+    }
+
+    /// 範囲内の要素を切り取り，移動後の先頭の位置がdestとなるように挿入する
+    /// NOTE: O(log N)
+    ///
+    /// # Panics
+    /// destが切り取った後の長さより大きい場合
+    pub fn move_range<R: RangeBounds<usize>>(&mut self, src: R, dest: usize) {
+        let (l, r) = to_index_range(&src, self.len());
+        assert!(dest <= self.len() - (r - l));
+        let (left, mid, right) = split3(self.root.take(), l, r);
+        let (left, right) = split(merge(left, right), dest);
+        self.root = merge(merge(left, mid), right);
     }
 
     pub fn bisect(&self, f: impl FnMut(&T) -> bool) -> usize {
-        bisect(self.root, f)
+        bisect(self.root, f, false)
     }
 
     pub fn lower_bound(&self, value: &T) -> usize
@@ -409,40 +684,82 @@ impl<T> AvlTreeVec<T> {
     }
 
     pub fn iter(&self) -> Iter<'_, T> {
-        Iter::new(self.root)
+        Iter::new(self.root, 0, self.len())
     }
 
     pub fn iter_mut(&mut self) -> IterMut<'_, T> {
-        IterMut::new(self.root)
+        IterMut::new(self.root, 0, self.len())
+    }
+
+    /// 範囲内の要素を返すイテレータ
+    /// NOTE: 構築はO(log N)
+    pub fn range<R: RangeBounds<usize>>(&self, range: R) -> Iter<'_, T> {
+        let (l, r) = to_index_range(&range, self.len());
+        Iter::new(self.root, l, r)
+    }
+
+    /// 範囲内の要素の可変参照を返すイテレータ
+    /// NOTE: 構築はO(log N)
+    pub fn range_mut<R: RangeBounds<usize>>(&mut self, range: R) -> IterMut<'_, T> {
+        let (l, r) = to_index_range(&range, self.len());
+        IterMut::new(self.root, l, r)
+    }
+
+    /// 範囲内の要素を切り離し，先頭から返すイテレータ
+    /// NOTE: 切り離しはO(log N)で，要素は返すときに解放する
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> IntoIter<T, S> {
+        let (l, r) = to_index_range(&range, self.len());
+        let (left, mid, right) = split3(self.root.take(), l, r);
+        self.root = merge(left, right);
+        IntoIter::new(mid, self.pool.share())
+    }
+
+    /// 範囲内の要素をreplace_withの要素で置き換え，取り除いた要素を先頭から返すイテレータ
+    /// Vec::spliceと異なり，置き換えは呼び出した時点で行われる
+    /// NOTE: 挿入する要素数Mに対してO(log N + M)で，取り除いた要素は返すときに解放する
+    pub fn splice<R, I>(&mut self, range: R, replace_with: I) -> IntoIter<T, S>
+    where
+        R: RangeBounds<usize>,
+        I: IntoIterator<Item = T>,
+    {
+        let (l, r) = to_index_range(&range, self.len());
+        let inserted = build_from_iter(replace_with, &mut self.pool);
+        let (left, removed, right) = split3(self.root.take(), l, r);
+        self.root = merge(merge(left, inserted), right);
+        IntoIter::new(removed, self.pool.share())
     }
 }
 
-impl<T> Default for AvlTreeVec<T> {
+impl<T, S: Storage> Default for AvlTreeVecIn<T, S> {
     fn default() -> Self {
-        Self { root: None }
+        Self {
+            root: None,
+            pool: NodePool::<T, S>::default(),
+            phantom: PhantomData,
+        }
     }
 }
 
-impl<T> Drop for AvlTreeVec<T> {
+impl<T, S: Storage> Drop for AvlTreeVecIn<T, S> {
     fn drop(&mut self) {
-        traverse_postorder(self.root, |node| free(node));
+        free_tree(self.root, &mut self.pool);
     }
 }
 
-impl<T> Index<usize> for AvlTreeVec<T> {
+impl<T, S: Storage> Index<usize> for AvlTreeVecIn<T, S> {
     type Output = T;
     fn index(&self, index: usize) -> &Self::Output {
         self.get(index).unwrap()
     }
 }
 
-impl<T> IndexMut<usize> for AvlTreeVec<T> {
+impl<T, S: Storage> IndexMut<usize> for AvlTreeVecIn<T, S> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         self.get_mut(index).unwrap()
     }
 }
 
-impl<'a, T> IntoIterator for &'a AvlTreeVec<T> {
+impl<'a, T, S: Storage> IntoIterator for &'a AvlTreeVecIn<T, S> {
     type IntoIter = Iter<'a, T>;
     type Item = &'a T;
 
@@ -451,7 +768,7 @@ impl<'a, T> IntoIterator for &'a AvlTreeVec<T> {
     }
 }
 
-impl<'a, T> IntoIterator for &'a mut AvlTreeVec<T> {
+impl<'a, T, S: Storage> IntoIterator for &'a mut AvlTreeVecIn<T, S> {
     type IntoIter = IterMut<'a, T>;
     type Item = &'a mut T;
 
@@ -460,137 +777,180 @@ impl<'a, T> IntoIterator for &'a mut AvlTreeVec<T> {
     }
 }
 
-impl<T> IntoIterator for AvlTreeVec<T> {
-    type IntoIter = IntoIter<T>;
+impl<T, S: Storage> IntoIterator for AvlTreeVecIn<T, S> {
+    type IntoIter = IntoIter<T, S>;
     type Item = T;
 
     fn into_iter(mut self) -> Self::IntoIter {
-        IntoIter::new(self.root.take())
+        IntoIter::new(self.root.take(), take(&mut self.pool))
     }
 }
 
-impl<T: PartialEq> PartialEq for AvlTreeVec<T> {
+impl<T: PartialEq, S: Storage> PartialEq for AvlTreeVecIn<T, S> {
     fn eq(&self, other: &Self) -> bool {
         self.iter().eq(other.iter())
     }
 }
 
-impl<T: Eq> Eq for AvlTreeVec<T> {}
+impl<T: Eq, S: Storage> Eq for AvlTreeVecIn<T, S> {}
 
-impl<T: PartialOrd> PartialOrd for AvlTreeVec<T> {
+impl<T: PartialOrd, S: Storage> PartialOrd for AvlTreeVecIn<T, S> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         self.iter().partial_cmp(other.iter())
     }
 }
 
-impl<T: Ord> Ord for AvlTreeVec<T> {
+impl<T: Ord, S: Storage> Ord for AvlTreeVecIn<T, S> {
     fn cmp(&self, other: &Self) -> Ordering {
         self.iter().cmp(other.iter())
     }
 }
 
-impl<T: Hash> Hash for AvlTreeVec<T> {
+impl<T: Hash, S: Storage> Hash for AvlTreeVecIn<T, S> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.iter().for_each(|item| item.hash(state));
     }
 }
 
-impl<T> Extend<T> for AvlTreeVec<T> {
+impl<T, S: Storage> Extend<T> for AvlTreeVecIn<T, S> {
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
-        iter.into_iter().for_each(|item| {
-            self.push_back(item);
-        });
+        self.insert_many(self.len(), iter);
     }
 }
 
-impl<'a, T: 'a + Copy> Extend<&'a T> for AvlTreeVec<T> {
+impl<'a, T: 'a + Copy, S: Storage> Extend<&'a T> for AvlTreeVecIn<T, S> {
     fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
         self.extend(iter.into_iter().cloned());
     }
 }
 
-impl<T> FromIterator<T> for AvlTreeVec<T> {
+impl<T, S: Storage> FromIterator<T> for AvlTreeVecIn<T, S> {
+    /// NOTE: O(N)
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
-        let mut res = Self::new();
-        res.extend(iter);
-        res
+        let mut pool = NodePool::<T, S>::default();
+        Self {
+            root: build_from_iter(iter, &mut pool),
+            pool,
+            phantom: PhantomData,
+        }
     }
 }
 
-impl<T> From<Vec<T>> for AvlTreeVec<T> {
+impl<T, S: Storage> From<Vec<T>> for AvlTreeVecIn<T, S> {
     fn from(v: Vec<T>) -> Self {
         Self::from_iter(v)
     }
 }
 
-impl<T, const N: usize> From<[T; N]> for AvlTreeVec<T> {
+impl<T, S: Storage, const N: usize> From<[T; N]> for AvlTreeVecIn<T, S> {
     fn from(v: [T; N]) -> Self {
         Self::from_iter(v)
     }
 }
 
-impl<T: Clone> Clone for AvlTreeVec<T> {
+impl<T: Clone, S: Storage> Clone for AvlTreeVecIn<T, S> {
     fn clone(&self) -> Self {
         Self::from_iter(self.iter().cloned())
     }
 }
 
-impl<T: Debug> Debug for AvlTreeVec<T> {
+impl<T: Debug, S: Storage> Debug for AvlTreeVecIn<T, S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_list().entries(self.iter()).finish()
     }
 }
 
-struct IterBase<'a, T> {
-    stack: Vec<NodePtr<T>>,
-    stack_rev: Vec<NodePtr<T>>,
-    phantom: PhantomData<&'a ()>,
+/// [l, r)のノードを前後から走査する
+/// 伝播していない反転の偶奇をノードと組にして持ち回る
+/// 構築時に範囲の両端まで降りるのでO(log N)，以降は1要素あたりならしO(1)
+struct IterBase<T> {
+    stack: Vec<(NodePtr<T>, bool)>,
+    stack_rev: Vec<(NodePtr<T>, bool)>,
+    /// まだ返していないノード数
+    len: usize,
 }
 
-impl<'a, T> IterBase<'a, T> {
-    fn new(root: Link<T>) -> Self {
+impl<T> IterBase<T> {
+    fn new(root: Link<T>, l: usize, r: usize) -> Self {
         let mut iter = Self {
             stack: vec![],
             stack_rev: vec![],
-            phantom: PhantomData::default(),
+            len: r - l,
         };
-        iter.push_left(root);
-        iter.push_right(root);
+
+        // l番目以降のノードのうち，根からl番目の位置までの経路上にあるものを積む
+        let (mut node, mut rev, mut index) = (root, false, l);
+        while let Some(n) = node {
+            let (left, right, child_rev) = unsafe { n.as_ref() }.children(rev);
+            let left_len = len(left);
+            if index <= left_len {
+                iter.stack.push((n, rev));
+                node = left;
+            } else {
+                node = right;
+                index -= left_len + 1;
+            }
+            rev = child_rev;
+        }
+
+        // r番目より前のノードのうち，根からr番目の位置までの経路上にあるものを積む
+        let (mut node, mut rev, mut index) = (root, false, r);
+        while let Some(n) = node {
+            let (left, right, child_rev) = unsafe { n.as_ref() }.children(rev);
+            let left_len = len(left);
+            if index > left_len {
+                iter.stack_rev.push((n, rev));
+                node = right;
+                index -= left_len + 1;
+            } else {
+                node = left;
+            }
+            rev = child_rev;
+        }
+
         iter
     }
 
-    fn push_left(&mut self, mut node: Link<T>) {
+    fn push_left(&mut self, mut node: Link<T>, mut rev: bool) {
         while let Some(n) = node {
-            self.stack.push(n);
-            node = unsafe { n.as_ref() }.left;
+            self.stack.push((n, rev));
+            (node, _, rev) = unsafe { n.as_ref() }.children(rev);
         }
     }
 
-    fn push_right(&mut self, mut node: Link<T>) {
+    fn push_right(&mut self, mut node: Link<T>, mut rev: bool) {
         while let Some(n) = node {
-            self.stack_rev.push(n);
-            node = unsafe { n.as_ref() }.right;
+            self.stack_rev.push((n, rev));
+            (_, node, rev) = unsafe { n.as_ref() }.children(rev);
         }
     }
 
     fn next(&mut self) -> Option<NodePtr<T>> {
-        let node = self.stack.pop()?;
-        self.push_left(unsafe { node.as_ref() }.right);
+        self.len = self.len.checked_sub(1)?;
+        let (node, rev) = self.stack.pop()?;
+        let (_, right, rev) = unsafe { node.as_ref() }.children(rev);
+        self.push_left(right, rev);
         Some(node)
     }
 
     fn next_back(&mut self) -> Option<NodePtr<T>> {
-        let node = self.stack_rev.pop()?;
-        self.push_right(unsafe { node.as_ref() }.left);
+        self.len = self.len.checked_sub(1)?;
+        let (node, rev) = self.stack_rev.pop()?;
+        let (left, _, rev) = unsafe { node.as_ref() }.children(rev);
+        self.push_right(left, rev);
         Some(node)
     }
 }
 
-pub struct Iter<'a, T>(IterBase<'a, T>);
+pub struct Iter<'a, T>(IterBase<T>, PhantomData<&'a T>);
+
+// NOTE: &AvlTreeVec<T>と同様
+unsafe impl<T: Sync> Send for Iter<'_, T> {}
+unsafe impl<T: Sync> Sync for Iter<'_, T> {}
 
 impl<'a, T: 'a> Iter<'a, T> {
-    fn new(root: Link<T>) -> Self {
-        Self(IterBase::new(root))
+    fn new(root: Link<T>, l: usize, r: usize) -> Self {
+        Self(IterBase::new(root, l, r), PhantomData)
     }
 }
 
@@ -599,6 +959,10 @@ impl<'a, T: 'a> Iterator for Iter<'a, T> {
     fn next(&mut self) -> Option<Self::Item> {
         self.0.next().map(|node| &unsafe { node.as_ref() }.value)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
 }
 
 impl<'a, T: 'a> DoubleEndedIterator for Iter<'a, T> {
@@ -609,11 +973,17 @@ impl<'a, T: 'a> DoubleEndedIterator for Iter<'a, T> {
     }
 }
 
-pub struct IterMut<'a, T>(IterBase<'a, T>);
+impl<'a, T: 'a> ExactSizeIterator for Iter<'a, T> {}
+
+pub struct IterMut<'a, T>(IterBase<T>, PhantomData<&'a mut T>);
+
+// NOTE: &mut AvlTreeVec<T>と同様
+unsafe impl<T: Send> Send for IterMut<'_, T> {}
+unsafe impl<T: Sync> Sync for IterMut<'_, T> {}
 
 impl<'a, T: 'a> IterMut<'a, T> {
-    fn new(root: Link<T>) -> Self {
-        Self(IterBase::new(root))
+    fn new(root: Link<T>, l: usize, r: usize) -> Self {
+        Self(IterBase::new(root, l, r), PhantomData)
     }
 }
 
@@ -624,6 +994,10 @@ impl<'a, T: 'a> Iterator for IterMut<'a, T> {
             .next()
             .map(|mut node| &mut unsafe { node.as_mut() }.value)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
 }
 
 impl<'a, T: 'a> DoubleEndedIterator for IterMut<'a, T> {
@@ -634,37 +1008,101 @@ impl<'a, T: 'a> DoubleEndedIterator for IterMut<'a, T> {
     }
 }
 
-pub struct IntoIter<T> {
-    iter: std::vec::IntoIter<T>,
+impl<'a, T: 'a> ExactSizeIterator for IterMut<'a, T> {}
+
+/// 切り離した部分木を所有し，要素を前後から返すイテレータ
+/// 返したノードは木から外して解放し，残りのノードはDropで解放する
+/// NOTE: 構築はO(log N)，以降は1要素あたりならしO(1)
+pub struct IntoIter<T, S: Storage = Boxed> {
+    /// 残っているノードからなる木の左端の経路(根が先頭)
+    left: Vec<NodePtr<T>>,
+    /// 残っているノードからなる木の右端の経路(根が先頭)
+    right: Vec<NodePtr<T>>,
+    len: usize,
+    pool: NodePool<T, S>,
 }
 
-impl<T> IntoIter<T> {
-    fn new(root: Link<T>) -> Self {
-        let mut stack = Vec::with_capacity(len(root));
-        traverse_inorder(root, |node| {
-            let boxed = unsafe { Box::from_raw(node.as_ptr()) };
-            stack.push(boxed.value)
-        });
-        IntoIter {
-            iter: stack.into_iter(),
+// NOTE: AvlTreeVec<T>と同様
+unsafe impl<T: Send, S: Storage> Send for IntoIter<T, S> {}
+unsafe impl<T: Sync, S: Storage> Sync for IntoIter<T, S> {}
+
+impl<T, S: Storage> IntoIter<T, S> {
+    fn new(root: Link<T>, pool: NodePool<T, S>) -> Self {
+        let mut iter = Self {
+            left: vec![],
+            right: vec![],
+            len: len(root),
+            pool,
+        };
+        iter.push_left(root);
+        iter.push_right(root);
+        iter
+    }
+
+    /// 経路上のノードは反転を伝播させておき，子を付け替えられるようにする
+    fn push_left(&mut self, mut node: Link<T>) {
+        while let Some(mut n) = node {
+            unsafe { n.as_mut() }.push();
+            self.left.push(n);
+            node = unsafe { n.as_ref() }.left;
+        }
+    }
+
+    fn push_right(&mut self, mut node: Link<T>) {
+        while let Some(mut n) = node {
+            unsafe { n.as_mut() }.push();
+            self.right.push(n);
+            node = unsafe { n.as_ref() }.right;
         }
     }
 }
 
-impl<T> Iterator for IntoIter<T> {
+impl<T, S: Storage> Drop for IntoIter<T, S> {
+    fn drop(&mut self) {
+        free_tree(self.left.first().copied(), &mut self.pool);
+    }
+}
+
+impl<T, S: Storage> Iterator for IntoIter<T, S> {
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next()
+        let node = self.left.pop()?;
+        self.len -= 1;
+        let right = unsafe { node.as_ref() }.right;
+        if let Some(mut parent) = self.left.last().copied() {
+            unsafe { parent.as_mut() }.left = right;
+        } else {
+            // 根を返したので，右端の経路から根を除く
+            self.right.remove(0);
+        }
+        self.push_left(right);
+        Some(unsafe { self.pool.dealloc(node) }.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
     }
 }
 
-impl<T> DoubleEndedIterator for IntoIter<T> {
+impl<T, S: Storage> DoubleEndedIterator for IntoIter<T, S> {
     fn next_back(&mut self) -> Option<Self::Item> {
-        self.iter.next_back()
+        let node = self.right.pop()?;
+        self.len -= 1;
+        let left = unsafe { node.as_ref() }.left;
+        if let Some(mut parent) = self.right.last().copied() {
+            unsafe { parent.as_mut() }.right = left;
+        } else {
+            // 根を返したので，左端の経路から根を除く
+            self.left.remove(0);
+        }
+        self.push_right(left);
+        Some(unsafe { self.pool.dealloc(node) }.value)
     }
 }
 
+impl<T, S: Storage> ExactSizeIterator for IntoIter<T, S> {}
+
 #[cfg(test)]
 mod tests {
     use super::AvlTreeVec;
@@ -740,6 +1178,7 @@ mod tests {
         assert!(tree.is_empty());
     }
 
+    #[allow(clippy::option_map_unit_fn)]
     #[test]
     fn test_get_mut() {
         let mut tree = AvlTreeVec::from([3, 1, 4, 1, 5]);
@@ -793,4 +1232,534 @@ mod tests {
         }
         assert!(v.iter().copied().eq([3, 5, 7, 9, 11]));
     }
+
+    #[test]
+    fn test_reverse() {
+        let mut v = AvlTreeVec::from_iter(0..10);
+        v.reverse(2..7);
+        assert!(v.iter().copied().eq([0, 1, 6, 5, 4, 3, 2, 7, 8, 9]));
+        v.reverse(..);
+        assert!(v.iter().copied().eq([9, 8, 7, 2, 3, 4, 5, 6, 1, 0]));
+        assert!(v.iter().rev().copied().eq([0, 1, 6, 5, 4, 3, 2, 7, 8, 9]));
+        assert_eq!(v[3], 2);
+        v.reverse(5..5);
+        v.reverse(9..=9);
+
+        let mut v = AvlTreeVec::from_iter(0..100);
+        v.reverse(..);
+        assert_eq!(v.bisect(|&x| x >= 30), 70);
+        assert_eq!(v.upper_bound_by(|x| 30.cmp(x)), 70);
+        v.iter_mut().for_each(|e| *e *= 2);
+        v.insert(50, -1);
+        assert_eq!(v.remove(0), Some(198));
+        let mut expected: Vec<_> = (0..99).rev().map(|x| x * 2).collect();
+        expected.insert(49, -1);
+        assert_eq!(v.clone().into_iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_reverse_random() {
+        use crate::{Arena, AvlTreeVecIn, Boxed, Storage};
+
+        fn check<S: Storage>() {
+            use rand::{thread_rng, Rng};
+
+            let mut rng = thread_rng();
+
+            for _ in 0..5 {
+                let mut avl = AvlTreeVecIn::<i32, S>::new();
+                let mut v = vec![];
+                for _ in 0..1000 {
+                    // 0: insert
+                    // 1: remove
+                    // 2: reverse
+                    // 3: get
+                    let t = rng.gen_range(0..4);
+                    let l = rng.gen_range(0..=v.len());
+                    let r = rng.gen_range(l..=v.len());
+                    match t {
+                        0 => {
+                            let x = rng.gen_range(-100..=100);
+                            avl.insert(l, x);
+                            v.insert(l, x);
+                        }
+                        1 => {
+                            let expected = (l < v.len()).then(|| v.remove(l));
+                            assert_eq!(avl.remove(l), expected);
+                        }
+                        2 => {
+                            avl.reverse(l..r);
+                            v[l..r].reverse();
+                        }
+                        3 => assert_eq!(avl.get(l), v.get(l)),
+                        _ => {}
+                    }
+                    assert_eq!(avl.len(), v.len());
+                }
+                assert!(avl.iter().eq(v.iter()));
+                assert!(avl.iter().rev().eq(v.iter().rev()));
+                assert!(avl.into_iter().eq(v.into_iter()));
+            }
+        }
+
+        check::<Boxed>();
+        check::<Arena>();
+    }
+
+    #[test]
+    fn test_rotate_and_move_range() {
+        let mut v = AvlTreeVec::from_iter(0..8);
+        v.rotate_left(1..6, 2);
+        assert!(v.iter().copied().eq([0, 3, 4, 5, 1, 2, 6, 7]));
+        v.rotate_right(1..6, 2);
+        assert!(v.iter().copied().eq([0, 1, 2, 3, 4, 5, 6, 7]));
+        v.rotate_left(.., 8);
+        v.rotate_right(3..3, 0);
+        assert!(v.iter().copied().eq(0..8));
+
+        v.move_range(1..3, 4);
+        assert!(v.iter().copied().eq([0, 3, 4, 5, 1, 2, 6, 7]));
+        v.move_range(6.., 0);
+        assert!(v.iter().copied().eq([6, 7, 0, 3, 4, 5, 1, 2]));
+        v.move_range(..0, 8);
+        assert!(v.iter().copied().eq([6, 7, 0, 3, 4, 5, 1, 2]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rotate_left_panic() {
+        let mut v = AvlTreeVec::from_iter(0..8);
+        v.rotate_left(2..4, 3);
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_rotate_and_move_range_random() {
+        use crate::{Arena, AvlTreeVecIn, Boxed, Storage};
+
+        fn check<S: Storage>() {
+            use rand::{thread_rng, Rng};
+
+            let mut rng = thread_rng();
+
+            for _ in 0..5 {
+                let mut avl = AvlTreeVecIn::<_, S>::from_iter(0..100);
+                let mut v: Vec<_> = (0..100).collect();
+                for _ in 0..1000 {
+                    // 0: rotate_left
+                    // 1: rotate_right
+                    // 2: move_range
+                    // 3: reverse
+                    let t = rng.gen_range(0..4);
+                    let l = rng.gen_range(0..=v.len());
+                    let r = rng.gen_range(l..=v.len());
+                    match t {
+                        0 => {
+                            let k = rng.gen_range(0..=r - l);
+                            avl.rotate_left(l..r, k);
+                            v[l..r].rotate_left(k);
+                        }
+                        1 => {
+                            let k = rng.gen_range(0..=r - l);
+                            avl.rotate_right(l..r, k);
+                            v[l..r].rotate_right(k);
+                        }
+                        2 => {
+                            let dest = rng.gen_range(0..=v.len() - (r - l));
+                            avl.move_range(l..r, dest);
+                            let moved: Vec<_> = v.drain(l..r).collect();
+                            v.splice(dest..dest, moved);
+                        }
+                        3 => {
+                            avl.reverse(l..r);
+                            v[l..r].reverse();
+                        }
+                        _ => {}
+                    }
+                    assert_eq!(avl.len(), v.len());
+                }
+                assert!(avl.iter().eq(v.iter()));
+            }
+        }
+
+        check::<Boxed>();
+        check::<Arena>();
+    }
+
+    #[test]
+    fn test_range() {
+        let mut v = AvlTreeVec::from_iter(0..10);
+        assert!(v.range(3..7).copied().eq(3..7));
+        assert!(v.range(..=2).rev().copied().eq([2, 1, 0]));
+        assert_eq!(v.range(4..4).next(), None);
+        assert_eq!(v.range(2..8).len(), 6);
+
+        let mut iter = v.range(2..5);
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next_back(), Some(&4));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+
+        v.range_mut(5..).for_each(|e| *e *= 10);
+        v.reverse(..);
+        assert!(v.range(1..6).copied().eq([80, 70, 60, 50, 4]));
+        assert!(v.range_mut(7..).rev().map(|e| *e).eq([0, 1, 2]));
+    }
+
+    #[test]
+    fn test_drain_and_splice() {
+        let mut v = AvlTreeVec::from_iter(0..10);
+        assert!(v.drain(2..5).eq([2, 3, 4]));
+        assert!(v.iter().copied().eq([0, 1, 5, 6, 7, 8, 9]));
+        assert!(v.drain(..0).eq([]));
+
+        assert!(v.splice(1..3, [10, 20, 30]).eq([1, 5]));
+        assert!(v.iter().copied().eq([0, 10, 20, 30, 6, 7, 8, 9]));
+        assert!(v.splice(8.., 40..43).eq([]));
+        assert!(v.splice(..4, []).eq([0, 10, 20, 30]));
+        assert!(v.iter().copied().eq([6, 7, 8, 9, 40, 41, 42]));
+        assert!(v.drain(..).eq([6, 7, 8, 9, 40, 41, 42]));
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn test_into_iter_double_ended_drop() {
+        use crate::{Arena, AvlTreeVecIn, Boxed, Storage};
+
+        fn check<S: Storage>() {
+            use std::rc::Rc;
+
+            let counter = Rc::new(());
+            let mut v = AvlTreeVecIn::<_, S>::from_iter((0..100).map(|i| (i, Rc::clone(&counter))));
+            v.reverse(10..60);
+            let mut expected: Vec<_> = (0..10).chain((10..60).rev()).chain(60..100).collect();
+            let mut iter = v.drain(5..95);
+            assert_eq!(Rc::strong_count(&counter), 101);
+
+            // 前後から交互に取り出し，返した要素はその場で解放される
+            let mut drained = expected.drain(5..95);
+            for _ in 0..20 {
+                assert_eq!(iter.next().map(|x| x.0), drained.next());
+                assert_eq!(iter.next_back().map(|x| x.0), drained.next_back());
+            }
+            assert_eq!(iter.len(), 50);
+            assert_eq!(Rc::strong_count(&counter), 61);
+
+            // 残りはDropで解放される
+            drop(iter);
+            drop(drained);
+            assert_eq!(Rc::strong_count(&counter), 11);
+            assert!(v.iter().map(|x| x.0).eq(expected));
+            assert!(is_balanced(v.root));
+
+            let mut iter = v.into_iter();
+            assert!(iter
+                .by_ref()
+                .rev()
+                .take(5)
+                .map(|x| x.0)
+                .eq([99, 98, 97, 96, 95]));
+            assert!(iter.map(|x| x.0).eq(0..5));
+            assert_eq!(Rc::strong_count(&counter), 1);
+        }
+
+        check::<Boxed>();
+        check::<Arena>();
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_range_drain_splice_random() {
+        use crate::{Arena, AvlTreeVecIn, Boxed, Storage};
+
+        fn check<S: Storage>() {
+            use rand::{thread_rng, Rng};
+
+            let mut rng = thread_rng();
+
+            for _ in 0..5 {
+                let mut avl = AvlTreeVecIn::<i32, S>::new();
+                let mut v = vec![];
+                for _ in 0..1000 {
+                    // 0: splice
+                    // 1: drain
+                    // 2: range
+                    // 3: range_mut
+                    // 4: reverse
+                    let t = rng.gen_range(0..5);
+                    let l = rng.gen_range(0..=v.len());
+                    let r = rng.gen_range(l..=v.len());
+                    match t {
+                        0 => {
+                            let k = rng.gen_range(0..5);
+                            let new: Vec<i32> = (0..k).map(|_| rng.gen_range(-100..=100)).collect();
+                            assert!(avl.splice(l..r, new.clone()).eq(v.splice(l..r, new)));
+                        }
+                        1 => assert!(avl.drain(l..r).eq(v.drain(l..r))),
+                        2 => {
+                            assert!(avl.range(l..r).eq(v[l..r].iter()));
+                            assert!(avl.range(l..r).rev().eq(v[l..r].iter().rev()));
+                        }
+                        3 => {
+                            let x = rng.gen_range(-100..=100);
+                            avl.range_mut(l..r).for_each(|e| *e += x);
+                            v[l..r].iter_mut().for_each(|e| *e += x);
+                        }
+                        4 => {
+                            avl.reverse(l..r);
+                            v[l..r].reverse();
+                        }
+                        _ => {}
+                    }
+                    assert_eq!(avl.len(), v.len());
+                }
+                assert!(avl.iter().eq(v.iter()));
+            }
+        }
+
+        check::<Boxed>();
+        check::<Arena>();
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_into_iter_double_ended_random() {
+        use crate::{Arena, AvlTreeVecIn, Boxed, Storage};
+
+        fn check<S: Storage>() {
+            use rand::{thread_rng, Rng};
+
+            let mut rng = thread_rng();
+
+            for _ in 0..100 {
+                let n = rng.gen_range(0..200);
+                let mut avl = AvlTreeVecIn::<_, S>::from_iter(0..n);
+                let mut v: Vec<_> = (0..n).collect();
+                for _ in 0..5 {
+                    let l = rng.gen_range(0..=n);
+                    let r = rng.gen_range(l..=n);
+                    avl.reverse(l..r);
+                    v[l..r].reverse();
+                }
+                let mut iter = avl.into_iter();
+                let mut expected = v.into_iter();
+                // 途中まで前後から取り出し，残りはDropで解放する
+                for _ in 0..rng.gen_range(0..=n) {
+                    if rng.gen_bool(0.5) {
+                        assert_eq!(iter.next(), expected.next());
+                    } else {
+                        assert_eq!(iter.next_back(), expected.next_back());
+                    }
+                    assert_eq!(iter.len(), expected.len());
+                }
+            }
+        }
+
+        check::<Boxed>();
+        check::<Arena>();
+    }
+
+    /// 各ノードの長さ・高さが正しく，AVL木の条件を満たしているか
+    fn is_balanced<T>(node: super::Link<T>) -> bool {
+        use super::{height, len};
+
+        node.is_none_or(|node| {
+            let node = unsafe { node.as_ref() };
+            let d = height(node.left) - height(node.right);
+            d.abs() <= 1
+                && node.height == height(node.left).max(height(node.right)) + 1
+                && node.len == len(node.left) + len(node.right) + 1
+                && is_balanced(node.left)
+                && is_balanced(node.right)
+        })
+    }
+
+    #[test]
+    fn test_from_iter_balanced() {
+        for n in [0usize, 1, 2, 3, 7, 8, 100, 1023, 1024] {
+            let v = AvlTreeVec::from_iter(0..n);
+            assert!(v.iter().copied().eq(0..n));
+            assert!(is_balanced(v.root));
+            // 完全にバランスしている
+            assert_eq!(
+                super::height(v.root),
+                (n + 1).next_power_of_two().trailing_zeros() as i32
+            );
+        }
+        let v = AvlTreeVec::from(vec!["a", "b", "c"]);
+        assert!(v.iter().copied().eq(["a", "b", "c"]));
+    }
+
+    #[test]
+    fn test_insert_many() {
+        let mut v = AvlTreeVec::from_iter(0..5);
+        v.insert_many(2, 10..13);
+        assert!(v.iter().copied().eq([0, 1, 10, 11, 12, 2, 3, 4]));
+        v.insert_vec(8, vec![20, 21]);
+        v.insert_vec(0, vec![]);
+        v.insert_many(0, [30]);
+        assert!(v
+            .iter()
+            .copied()
+            .eq([30, 0, 1, 10, 11, 12, 2, 3, 4, 20, 21]));
+        v.extend([40, 41]);
+        assert_eq!(v.len(), 13);
+        assert_eq!(v.back(), Some(&41));
+        assert!(is_balanced(v.root));
+    }
+
+    #[test]
+    fn test_insert_many_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        use std::rc::Rc;
+
+        let counter = Rc::new(());
+        let mut v = AvlTreeVec::from_iter((0..5).map(|_| Rc::clone(&counter)));
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            v.extend((0..10).map(|i| {
+                assert!(i < 3);
+                Rc::clone(&counter)
+            }))
+        }));
+        assert!(res.is_err());
+        // 既存の要素は残り，途中まで集めた要素は解放される
+        assert_eq!(v.len(), 5);
+        assert_eq!(Rc::strong_count(&counter), 6);
+        drop(v);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_insert_many_random() {
+        use crate::{Arena, AvlTreeVecIn, Boxed, Storage};
+
+        fn check<S: Storage>() {
+            use rand::{thread_rng, Rng};
+
+            let mut rng = thread_rng();
+
+            for _ in 0..5 {
+                let mut avl = AvlTreeVecIn::<i32, S>::new();
+                let mut v = vec![];
+                for _ in 0..200 {
+                    let index = rng.gen_range(0..=v.len());
+                    let k = rng.gen_range(0..50);
+                    let new: Vec<i32> = (0..k).map(|_| rng.gen_range(-100..=100)).collect();
+                    if rng.gen_bool(0.5) {
+                        avl.insert_many(index, new.iter().copied());
+                    } else {
+                        avl.insert_vec(index, new.clone());
+                    }
+                    v.splice(index..index, new);
+                    assert_eq!(avl.len(), v.len());
+                }
+                assert!(is_balanced(avl.root));
+                assert!(avl.iter().eq(v.iter()));
+            }
+        }
+
+        check::<Boxed>();
+        check::<Arena>();
+    }
+
+    #[test]
+    fn test_send_sync() {
+        use crate::{Arena, ArenaAvlTreeVec, IntoIter, Iter, IterMut};
+        use std::thread;
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<AvlTreeVec<String>>();
+        assert_send_sync::<Iter<'_, String>>();
+        assert_send_sync::<IterMut<'_, String>>();
+        assert_send_sync::<IntoIter<String>>();
+        assert_send_sync::<ArenaAvlTreeVec<String>>();
+        assert_send_sync::<IntoIter<String, Arena>>();
+
+        let mut v = thread::spawn(|| AvlTreeVec::from_iter(0..10))
+            .join()
+            .unwrap();
+        thread::scope(|s| {
+            let iter = v.iter_mut();
+            s.spawn(move || iter.for_each(|e| *e *= 2));
+        });
+        thread::scope(|s| {
+            s.spawn(|| assert_eq!(v.iter().sum::<i32>(), 90));
+            s.spawn(|| assert_eq!(v[3], 6));
+        });
+    }
+
+    #[allow(deprecated)]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_arena_random() {
+        use crate::ArenaAvlTreeVec;
+        use rand::{thread_rng, Rng};
+
+        let mut rng = thread_rng();
+
+        for _ in 0..5 {
+            let mut avl = ArenaAvlTreeVec::new();
+            let mut v = vec![];
+            for _ in 0..1000 {
+                // 0: insert
+                // 1: remove
+                // 2: split_off + append
+                // 3: get
+                // 4: clone
+                let t = rng.gen_range(0..5);
+                let index = rng.gen_range(0..=v.len());
+                match t {
+                    0 => {
+                        let x = rng.gen_range(-100..=100).to_string();
+                        avl.insert(index, x.clone());
+                        v.insert(index, x);
+                    }
+                    1 => {
+                        let expected = (index < v.len()).then(|| v.remove(index));
+                        assert_eq!(avl.remove(index), expected);
+                    }
+                    2 => {
+                        let mut right = avl.split_off(index);
+                        assert!(right.iter().eq(v[index..].iter()));
+                        right.push_front("x".to_string());
+                        v.insert(index, "x".to_string());
+                        avl.append(&mut right);
+                    }
+                    3 => assert_eq!(avl.get(index), v.get(index)),
+                    4 => avl = avl.clone(),
+                    _ => {}
+                }
+                assert_eq!(avl.len(), v.len());
+            }
+            assert!(is_balanced(avl.root));
+            assert!(avl.iter().eq(v.iter()));
+            assert!(avl.into_iter().eq(v.into_iter()));
+        }
+    }
+
+    #[test]
+    fn test_arena_drop() {
+        use crate::ArenaAvlTreeVec;
+        use std::rc::Rc;
+
+        let counter = Rc::new(());
+        let mut v = ArenaAvlTreeVec::from_iter((0..100).map(|_| Rc::clone(&counter)));
+        let mut right = v.split_off(50);
+        v.remove(0);
+        assert_eq!(Rc::strong_count(&counter), 100);
+        drop(right.split_off(20));
+        assert_eq!(Rc::strong_count(&counter), 70);
+        v.append(&mut right);
+        assert_eq!(v.len(), 69);
+        v.clear();
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
 }